@@ -0,0 +1,244 @@
+use crate::oxide::*;
+use std::env::consts::{DLL_PREFIX, DLL_SUFFIX};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::time::{Duration, Instant, SystemTime};
+
+type GameGetApi = extern "C" fn() -> GameApi;
+type GameLayoutFingerprint = extern "C" fn() -> LayoutFingerprint;
+
+// How long the library has to stay untouched before it's loaded,
+// so we don't grab it while the linker is still writing it
+const RELOAD_SETTLE_TIME: Duration = Duration::from_millis(200);
+
+// oxide.dll on windows, liboxide.so on linux
+pub fn lib_file_name(name: &str) -> String {
+    format!("{}{}{}", DLL_PREFIX, name, DLL_SUFFIX)
+}
+
+pub fn default_lib_path() -> PathBuf {
+    Path::new("../oxide/target/debug").join(lib_file_name("oxide"))
+}
+
+// A copy of the game library that is loaded and has handed over its GameApi
+struct LoadedLib {
+    library: libloading::Library,
+    temp_path: PathBuf,
+    api: GameApi
+}
+
+impl LoadedLib {
+    fn unload(self) {
+        drop(self.library);
+
+        // Only possible now that nothing has the copy loaded anymore
+        let _ = fs::remove_file(self.temp_path);
+    }
+}
+
+// The game code the host calls into. Either a hot reloadable copy of the oxide library
+// or, when there is no library (headless), the oxide code linked into the host.
+pub struct GameCode {
+    lib_path: Option<PathBuf>,
+    loaded: Option<LoadedLib>,
+    load_count: u32,
+    // Modification time of the last library we tried to load, good or bad
+    last_modified: Option<SystemTime>,
+    // A newer library we've seen but that hasn't settled yet
+    pending_modified: Option<(SystemTime, Instant)>
+}
+
+impl GameCode {
+    pub fn linked() -> GameCode {
+        GameCode {
+            lib_path: None,
+            loaded: None,
+            load_count: 0,
+            last_modified: None,
+            pending_modified: None
+        }
+    }
+
+    // Falls back to the linked code if the library can't be loaded,
+    // it keeps being watched so the next good build gets picked up
+    pub fn load(lib_path: PathBuf) -> GameCode {
+        let mut game_code = GameCode::linked();
+        game_code.lib_path = Some(lib_path);
+
+        if !game_code.swap_lib() {
+            eprintln!("Running the game code linked into the host instead");
+        }
+
+        game_code
+    }
+
+    // Swaps to a fresh copy of the library and lets the game know. Returns true on success.
+    pub fn reload(&mut self, memory: &mut GameMemory) -> bool {
+        if !self.swap_lib() {
+            return false;
+        }
+
+        unsafe { (self.api().on_reload)(memory); }
+        true
+    }
+
+    // Reloads once a new build of the library has landed, returns true if it did
+    pub fn reload_if_changed(&mut self, memory: &mut GameMemory) -> bool {
+        let modified = match &self.lib_path {
+            Some(path) => match modified_time(path) {
+                Some(value) => value,
+                None => return false
+            },
+            None => return false
+        };
+
+        if Some(modified) == self.last_modified {
+            self.pending_modified = None;
+            return false;
+        }
+
+        match self.pending_modified {
+            Some((pending, seen_at)) if pending == modified => {
+                if seen_at.elapsed() < RELOAD_SETTLE_TIME {
+                    return false;
+                }
+            },
+            _ => {
+                self.pending_modified = Some((modified, Instant::now()));
+                return false;
+            }
+        }
+
+        self.reload(memory)
+    }
+
+    pub fn init(&self, memory: &mut GameMemory) {
+        unsafe { (self.api().init)(memory); }
+    }
+
+    pub fn update(&self, memory: &mut GameMemory, input_controller: &mut InputController, screen: &WindowDimensions) {
+        unsafe { (self.api().update)(memory, input_controller, screen); }
+    }
+
+    pub fn render(&self, memory: &mut GameMemory, buffer: &mut OffscreenBuffer, blend_factor: f32) {
+        unsafe { (self.api().render)(memory, buffer, blend_factor); }
+    }
+
+    pub fn get_sound_samples(&self, memory: &mut GameMemory, sound_buffer: &mut SoundOutputBuffer) {
+        unsafe { (self.api().get_sound_samples)(memory, sound_buffer); }
+    }
+
+    pub fn shutdown(&self, memory: &mut GameMemory) {
+        unsafe { (self.api().shutdown)(memory); }
+    }
+
+    fn api(&self) -> GameApi {
+        match &self.loaded {
+            Some(lib) => lib.api,
+            None => game_get_api()
+        }
+    }
+
+    // The current code is only unloaded once the new one has loaded and checked out,
+    // otherwise it keeps running
+    fn swap_lib(&mut self) -> bool {
+        let lib_path = match &self.lib_path {
+            Some(value) => value.clone(),
+            None => return false
+        };
+
+        self.last_modified = modified_time(&lib_path);
+        self.pending_modified = None;
+
+        match self.load_lib(&lib_path) {
+            Ok(new_lib) => {
+                if let Some(old_lib) = self.loaded.replace(new_lib) {
+                    old_lib.unload();
+                }
+                true
+            },
+            Err(error) => {
+                eprintln!("Unable to load oxide lib, keeping the current game code: {}", error);
+                false
+            }
+        }
+    }
+
+    fn load_lib(&mut self, lib_path: &Path) -> Result<LoadedLib, String> {
+        // Each load gets its own copy, so neither the linker nor a library that
+        // is still loaded ever holds a lock on the file we're writing
+        self.load_count += 1;
+        let temp_name = lib_file_name(&format!("oxide_temp_{}_{}", process::id(), self.load_count));
+        let temp_path = lib_path.with_file_name(temp_name);
+
+        if let Err(error) = fs::copy(lib_path, &temp_path) {
+            return Err(format!("Unable to copy {} to temp: {}", lib_path.display(), error));
+        }
+
+        unsafe {
+            let library = match libloading::Library::new(&temp_path) {
+                Ok(value) => value,
+                Err(error) => {
+                    let _ = fs::remove_file(&temp_path);
+                    return Err(format!("Unable to load {}: {}", temp_path.display(), error));
+                }
+            };
+
+            // The library has to be built against the same struct layouts as the host,
+            // otherwise every pointer we hand it is read wrong
+            let layout_error = match library.get::<GameLayoutFingerprint>(b"game_layout_fingerprint") {
+                Ok(func) => {
+                    let host_layout = layout_fingerprint();
+                    let lib_layout = func();
+                    if lib_layout == host_layout {
+                        None
+                    } else {
+                        Some(format!("Layout mismatch between host and lib ({}), rebuild the host",
+                            host_layout.differences(&lib_layout).join(", ")))
+                    }
+                },
+                Err(error) => Some(format!("Unable to get game_layout_fingerprint: {}", error))
+            };
+
+            if let Some(error) = layout_error {
+                drop(library);
+                let _ = fs::remove_file(&temp_path);
+                return Err(error);
+            }
+
+            let api = match library.get::<GameGetApi>(b"game_get_api") {
+                Ok(func) => func(),
+                Err(error) => {
+                    drop(library);
+                    let _ = fs::remove_file(&temp_path);
+                    return Err(format!("Unable to get game_get_api: {}", error));
+                }
+            };
+
+            if api.version != GAME_API_VERSION {
+                drop(library);
+                let _ = fs::remove_file(&temp_path);
+                return Err(format!("Game API version mismatch, host has {} and lib has {}", GAME_API_VERSION, api.version));
+            }
+
+            Ok(LoadedLib {
+                library,
+                temp_path,
+                api
+            })
+        }
+    }
+}
+
+impl Drop for GameCode {
+    fn drop(&mut self) {
+        if let Some(lib) = self.loaded.take() {
+            lib.unload();
+        }
+    }
+}
+
+fn modified_time(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -0,0 +1,305 @@
+use crate::oxide::*;
+use crate::game_code::GameCode;
+use crate::platform::*;
+use crate::recording::*;
+use std::cmp::max;
+use std::ffi::c_void;
+use std::fs::File;
+use std::io::BufWriter;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// Runs the game without a window, straight into a buffer in plain memory.
+// Used to look at rendering output on machines without win32 and in automated checks.
+pub struct HeadlessConfig {
+    pub frames: u32,
+    pub delta_time: f32,
+    pub width: u32,
+    pub height: u32,
+    pub output_path: String,
+    pub write_every_frame: bool,
+    // Recording to play back from its first frame
+    pub replay_path: Option<String>,
+    // Reference image the output has to match, the process exits with 1 if it doesn't.
+    // See IMAGE_CHANNEL_TOLERANCE and MAX_DIFFERING_PIXELS for how closely.
+    pub compare_path: Option<String>
+}
+
+impl Default for HeadlessConfig {
+    fn default() -> Self {
+        HeadlessConfig {
+            frames: 1,
+            delta_time: 1000.0 / 60.0,
+            width: 1280,
+            height: 720,
+            output_path: String::from("frame.ppm"),
+            write_every_frame: false,
+            replay_path: None,
+            compare_path: None
+        }
+    }
+}
+
+impl HeadlessConfig {
+    // Reads --frames, --delta-time, --width, --height, --output, --every-frame, --replay and --compare.
+    // With --replay, runs for as many frames as the recording has and at the window size it was
+    // recorded at, unless --frames, --width or --height say otherwise.
+    pub fn from_args(args: &[String]) -> HeadlessConfig {
+        let mut config = HeadlessConfig::default();
+        let mut frames_given = false;
+        let mut width_given = false;
+        let mut height_given = false;
+
+        let mut i = 0;
+        while i < args.len() {
+            let value = args.get(i + 1);
+            match args[i].as_str() {
+                "--frames" => {
+                    config.frames = parse_arg(&args[i], value);
+                    frames_given = true;
+                },
+                "--delta-time" => config.delta_time = parse_arg(&args[i], value),
+                "--width" => {
+                    config.width = parse_arg(&args[i], value);
+                    width_given = true;
+                },
+                "--height" => {
+                    config.height = parse_arg(&args[i], value);
+                    height_given = true;
+                },
+                "--output" => config.output_path = parse_arg(&args[i], value),
+                "--replay" => config.replay_path = Some(parse_arg(&args[i], value)),
+                "--compare" => config.compare_path = Some(parse_arg(&args[i], value)),
+                "--every-frame" => {
+                    config.write_every_frame = true;
+                    i += 1;
+                    continue;
+                },
+                _ => {
+                    i += 1;
+                    continue;
+                }
+            }
+
+            i += 2;
+        }
+
+        if let Some(replay_path) = &config.replay_path {
+            let summary = match read_recording_summary(Path::new(replay_path)) {
+                Some(summary) => summary,
+                None => panic!("{} isn't a recording this build can play", replay_path)
+            };
+
+            if !frames_given {
+                config.frames = summary.frame_count as u32;
+            }
+
+            // The updates get the recorded size either way, this only makes the output look like it did
+            if let Some(window_size) = summary.window_size {
+                if !width_given {
+                    config.width = window_size.width;
+                }
+                if !height_given {
+                    config.height = window_size.height;
+                }
+            }
+        }
+
+        config
+    }
+}
+
+fn parse_arg<T: std::str::FromStr>(name: &str, value: Option<&String>) -> T {
+    match value.map(|value| value.parse::<T>()) {
+        Some(Ok(value)) => value,
+        _ => panic!("Invalid or missing value for {}", name)
+    }
+}
+
+pub struct HeadlessPlatform {
+    config: HeadlessConfig,
+    pixels: Vec<u32>,
+    buffer: OffscreenBuffer,
+    frames_presented: u32
+}
+
+impl HeadlessPlatform {
+    pub fn new(config: HeadlessConfig) -> Result<HeadlessPlatform, String> {
+        // The game loop waits out a zero sized window without drawing, which would never end here
+        if config.width == 0 || config.height == 0 {
+            return Err(format!("Window size {}x{} has to be at least 1x1", config.width, config.height));
+        }
+
+        let mut pixels: Vec<u32> = vec![0; (config.width * config.height) as usize];
+
+        let buffer = OffscreenBuffer {
+            memory: pixels.as_mut_ptr() as *mut c_void,
+            width: config.width,
+            height: config.height,
+            pitch: config.width * 4,
+            bytes_per_pixel: 4,
+            pixel_format: PixelFormat::Bgra8
+        };
+
+        Ok(HeadlessPlatform {
+            config,
+            pixels,
+            buffer,
+            frames_presented: 0
+        })
+    }
+}
+
+impl Platform for HeadlessPlatform {
+    fn poll_input(&mut self, _input: &mut InputController) -> PlatformRequests {
+        PlatformRequests {
+            quit: self.frames_presented >= self.config.frames,
+            ..Default::default()
+        }
+    }
+
+    fn back_buffer(&mut self) -> &mut OffscreenBuffer {
+        &mut self.buffer
+    }
+
+    fn present_buffer(&mut self) {
+        let frame = self.frames_presented;
+        self.frames_presented += 1;
+
+        let is_last_frame = self.frames_presented == self.config.frames;
+        if self.config.write_every_frame || is_last_frame {
+            let path = if self.config.write_every_frame {
+                numbered_path(&self.config.output_path, frame)
+            } else {
+                self.config.output_path.clone()
+            };
+
+            write_ppm(&path, &self.pixels, self.config.width, self.config.height)
+                .expect("Unable to write frame");
+        }
+    }
+
+    fn window_size(&self) -> WindowDimensions {
+        WindowDimensions { width: self.config.width, height: self.config.height }
+    }
+
+    // Time only moves forward by a fixed step per frame so runs are reproducible
+    fn now(&self) -> f64 {
+        self.frames_presented as f64 * self.config.delta_time as f64
+    }
+
+    fn sleep(&self, _milliseconds: f64) {}
+}
+
+pub fn start_program(config: HeadlessConfig) {
+    let output_path = config.output_path.clone();
+    let compare_path = config.compare_path.clone();
+
+    if let Err(error) = run_game(config) {
+        eprintln!("Unable to run headless: {}", error);
+        std::process::exit(1);
+    }
+
+    if let Some(compare_path) = compare_path {
+        let difference_count = compare_ppm(&output_path, &compare_path);
+        if difference_count > MAX_DIFFERING_PIXELS {
+            println!("{} differs from {} in {} pixels", output_path, compare_path, difference_count);
+            std::process::exit(1);
+        }
+        println!("{} matches {}", output_path, compare_path);
+    }
+}
+
+pub fn run_game(config: HeadlessConfig) -> Result<(), String> {
+    let mut platform = HeadlessPlatform::new(config)?;
+    let mut recorder = match &platform.config.replay_path {
+        Some(path) => InputRecorder::replaying(PathBuf::from(path)),
+        None => InputRecorder::new(PathBuf::from(DEFAULT_RECORDING_PATH))
+    };
+
+    // One update per frame, and no waiting around since the clock is made up anyway
+    let loop_config = LoopConfig {
+        update_step: platform.config.delta_time as f64,
+        target_fps: None
+    };
+
+    run_game_loop(&mut platform, &mut GameCode::linked(), &mut recorder, loop_config);
+    Ok(())
+}
+
+// How far apart a color channel can be in two images and still count as the same. sin, cos, ln and
+// the rest don't round the same way on every platform and toolchain, which can nudge a blend by one.
+pub const IMAGE_CHANNEL_TOLERANCE: u8 = 2;
+// How many pixels can differ by more than that before two images count as different, for the odd
+// edge pixel that rounding tips over into another one
+pub const MAX_DIFFERING_PIXELS: usize = 16;
+
+// How many pixels of the two images have a channel more than IMAGE_CHANNEL_TOLERANCE apart,
+// all of them if they're not even the same size
+pub fn compare_ppm(path: &str, reference_path: &str) -> usize {
+    let (width, height, pixels) = read_ppm(path).unwrap_or_else(|error| panic!("Unable to read {}: {}", path, error));
+    let (reference_width, reference_height, reference_pixels) = read_ppm(reference_path)
+        .unwrap_or_else(|error| panic!("Unable to read {}: {}", reference_path, error));
+
+    if width != reference_width || height != reference_height {
+        println!("{} is {}x{} but {} is {}x{}", path, width, height, reference_path, reference_width, reference_height);
+        return max(width * height, reference_width * reference_height) as usize;
+    }
+
+    pixels.chunks(3).zip(reference_pixels.chunks(3))
+        .filter(|(pixel, reference_pixel)| pixel.iter().zip(reference_pixel.iter())
+            .any(|(channel, reference_channel)| channel.abs_diff(*reference_channel) > IMAGE_CHANNEL_TOLERANCE))
+        .count()
+}
+
+// Only reads what write_ppm writes, width, height and the RGB bytes
+fn read_ppm(path: &str) -> std::io::Result<(u32, u32, Vec<u8>)> {
+    let bytes = std::fs::read(path)?;
+    let invalid = || std::io::Error::new(std::io::ErrorKind::InvalidData, "not a binary PPM");
+
+    // Header is four whitespace separated fields, then a single whitespace byte before the pixels
+    let mut fields = Vec::new();
+    let mut start = 0;
+    let mut i = 0;
+    while fields.len() < 4 && i < bytes.len() {
+        if bytes[i].is_ascii_whitespace() {
+            if i > start {
+                fields.push(std::str::from_utf8(&bytes[start..i]).map_err(|_| invalid())?);
+            }
+            start = i + 1;
+        }
+        i += 1;
+    }
+
+    if fields.len() < 4 || fields[0] != "P6" || fields[3] != "255" {
+        return Err(invalid());
+    }
+
+    let width: u32 = fields[1].parse().map_err(|_| invalid())?;
+    let height: u32 = fields[2].parse().map_err(|_| invalid())?;
+    let pixels = bytes.get(i..i + (width * height * 3) as usize).ok_or_else(invalid)?;
+
+    Ok((width, height, pixels.to_vec()))
+}
+
+// frame.ppm -> frame_0007.ppm
+fn numbered_path(path: &str, frame: u32) -> String {
+    match path.rfind('.') {
+        Some(index) => format!("{}_{:04}{}", &path[..index], frame, &path[index..]),
+        None => format!("{}_{:04}", path, frame)
+    }
+}
+
+// Binary PPM (P6), pixels are 0xAARRGGBB and alpha is dropped
+pub fn write_ppm(path: &str, pixels: &[u32], width: u32, height: u32) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+
+    write!(writer, "P6\n{} {}\n255\n", width, height)?;
+
+    for pixel in pixels {
+        let rgb = [(pixel >> 16) as u8, (pixel >> 8) as u8, *pixel as u8];
+        writer.write_all(&rgb)?;
+    }
+
+    writer.flush()
+}
@@ -0,0 +1,410 @@
+// X11 names its event and keysym constants in CamelCase
+#![allow(non_upper_case_globals)]
+
+use crate::oxide::*;
+use crate::game_code::GameCode;
+use crate::linux_gamepad::LinuxGamepads;
+use crate::platform::*;
+use crate::recording::{InputRecorder, DEFAULT_RECORDING_PATH};
+use crate::services;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use std::ffi::c_void;
+use std::mem::MaybeUninit;
+use std::os::raw::{c_char, c_int, c_uint};
+use std::ptr::null;
+use std::ptr::null_mut;
+use x11::keysym::*;
+use x11::xlib::*;
+
+// Same as WHEEL_DELTA on win32 so the game sees the same values on both platforms
+const WHEEL_STEP: i16 = 120;
+
+struct X11Window {
+    display: *mut Display,
+    window: Window,
+    gc: GC,
+    visual: *mut Visual,
+    depth: c_int,
+    wm_delete_window: Atom,
+    // Shown instead of the normal one in relative mouse mode
+    blank_cursor: Cursor
+}
+
+// XImage wraps the pixels so XPutImage can send them to the server
+struct X11OffscreenBuffer {
+    image: *mut XImage,
+    pixels: Vec<u32>,
+    buffer: OffscreenBuffer
+}
+
+pub struct LinuxPlatform {
+    window: X11Window,
+    back_buffer: X11OffscreenBuffer,
+    gamepads: LinuxGamepads,
+    cursor: CursorTracker
+}
+
+impl Platform for LinuxPlatform {
+    fn poll_input(&mut self, input: &mut InputController) -> PlatformRequests {
+        unsafe {
+            let requests = process_pending_events(&self.window, &mut self.cursor, input);
+            update_relative_mouse(&self.window, &mut self.cursor, input);
+            self.gamepads.poll(&mut input.gamepads);
+
+            let dimensions = get_window_dimensions(&self.window);
+            if dimensions.width != self.back_buffer.buffer.width || dimensions.height != self.back_buffer.buffer.height {
+                resize_back_buffer(&self.window, &mut self.back_buffer, dimensions.width, dimensions.height);
+            }
+
+            requests
+        }
+    }
+
+    fn back_buffer(&mut self) -> &mut OffscreenBuffer {
+        &mut self.back_buffer.buffer
+    }
+
+    fn present_buffer(&mut self) {
+        unsafe { copy_buffer_to_window(&self.window, &self.back_buffer); }
+    }
+
+    fn window_size(&self) -> WindowDimensions {
+        unsafe { get_window_dimensions(&self.window) }
+    }
+
+    fn now(&self) -> f64 {
+        services::now()
+    }
+
+    fn sleep(&self, milliseconds: f64) {
+        thread::sleep(Duration::from_secs_f64(milliseconds / 1000.0));
+    }
+}
+
+pub fn start_program(mut game_code: GameCode, loop_config: LoopConfig) {
+    unsafe {
+        let window = create_window().expect("Unable to create window");
+
+        let window_size = get_window_dimensions(&window);
+
+        let mut back_buffer = X11OffscreenBuffer {
+            image: null_mut(),
+            pixels: Vec::new(),
+            buffer: OffscreenBuffer {
+                memory: null_mut(),
+                width: 0,
+                height: 0,
+                pitch: 0,
+                bytes_per_pixel: 0,
+                pixel_format: PixelFormat::Bgra8
+            }
+        };
+
+        resize_back_buffer(&window, &mut back_buffer, window_size.width, window_size.height);
+
+        let mut platform = LinuxPlatform { window, back_buffer, gamepads: LinuxGamepads::default(), cursor: CursorTracker::default() };
+
+        run_game_loop(&mut platform, &mut game_code, &mut InputRecorder::new(PathBuf::from(DEFAULT_RECORDING_PATH)), loop_config);
+
+        destroy_back_buffer(&mut platform.back_buffer);
+        XFreeCursor(platform.window.display, platform.window.blank_cursor);
+        XDestroyWindow(platform.window.display, platform.window.window);
+        XCloseDisplay(platform.window.display);
+    }
+}
+
+unsafe fn create_window() -> Result<X11Window, String> {
+    let display = XOpenDisplay(null());
+    if display.is_null() {
+        return Err(String::from("Unable to open X display"));
+    }
+
+    let screen = XDefaultScreen(display);
+    let root = XRootWindow(display, screen);
+    let black = XBlackPixel(display, screen);
+
+    let window = XCreateSimpleWindow(display, root, 0, 0, 1280, 720, 0, black, black);
+
+    XSelectInput(
+        display,
+        window,
+        KeyPressMask | KeyReleaseMask |
+        ButtonPressMask | ButtonReleaseMask | PointerMotionMask |
+        StructureNotifyMask | ExposureMask);
+
+    XStoreName(display, window, c"Oxide".as_ptr() as *const c_char);
+
+    // Ask the window manager to send a message instead of killing the connection on close
+    let mut wm_delete_window = XInternAtom(display, c"WM_DELETE_WINDOW".as_ptr() as *const c_char, False);
+    XSetWMProtocols(display, window, &mut wm_delete_window, 1);
+
+    // Held keys send repeated presses without the fake releases in between
+    XkbSetDetectableAutoRepeat(display, True, null_mut());
+
+    // A cursor made of a single transparent pixel, X has no way to just hide it
+    let blank_data: [c_char; 1] = [0];
+    let blank_pixmap = XCreateBitmapFromData(display, window, blank_data.as_ptr(), 1, 1);
+    let mut blank_color: XColor = MaybeUninit::zeroed().assume_init();
+    let blank_cursor = XCreatePixmapCursor(display, blank_pixmap, blank_pixmap, &mut blank_color, &mut blank_color, 0, 0);
+    XFreePixmap(display, blank_pixmap);
+
+    XMapWindow(display, window);
+    XFlush(display);
+
+    Ok(X11Window {
+        display,
+        window,
+        gc: XDefaultGC(display, screen),
+        visual: XDefaultVisual(display, screen),
+        depth: XDefaultDepth(display, screen),
+        wm_delete_window,
+        blank_cursor
+    })
+}
+
+unsafe fn process_pending_events(window: &X11Window, cursor: &mut CursorTracker, input: &mut InputController) -> PlatformRequests {
+    let mut requests = PlatformRequests::default();
+
+    while XPending(window.display) > 0 {
+        let mut event = MaybeUninit::<XEvent>::uninit();
+        XNextEvent(window.display, event.as_mut_ptr());
+        let mut event = event.assume_init();
+
+        match event.get_type() {
+            ClientMessage if event.client_message.data.get_long(0) as Atom == window.wm_delete_window => {
+                requests.quit = true;
+            },
+            DestroyNotify => {
+                requests.quit = true;
+            },
+            KeyPress | KeyRelease => {
+                let is_down = event.get_type() == KeyPress;
+                let keysym = XLookupKeysym(&mut event.key, 0) as c_uint;
+                let time = services::now();
+
+                let key = key_from_keysym(keysym);
+
+                if is_down {
+                    push_text_events(&mut event.key, time, input);
+                }
+
+                // With detectable auto repeat a held key only repeats the press, which shows up here.
+                // Repeats still type text but aren't another KeyDown.
+                let was_down = input.keyboard.set_is_down(key, is_down);
+                if was_down != is_down {
+                    input.events.push(time, if is_down { InputEvent::KeyDown(key) } else { InputEvent::KeyUp(key) });
+                }
+
+                if !was_down && is_down {
+                    // alt + F4
+                    if key == Key::F4 && event.key.state & Mod1Mask != 0 {
+                        requests.quit = true;
+                    } else {
+                        request_for_key(key, &mut requests);
+                    }
+                }
+            },
+            // While a button is held X grabs the pointer for us, so these keep coming from outside
+            // the window during a drag, with coordinates past its edges
+            MotionNotify => {
+                let pos = Vector2i32 { x: event.motion.x, y: event.motion.y };
+                cursor.cursor_moved(pos, services::now(), input);
+            },
+            ButtonPress | ButtonRelease => {
+                let is_down = event.get_type() == ButtonPress;
+                let time = services::now();
+
+                let mouse_button = match event.button.button {
+                    Button1 => {
+                        input.mouse_state.left.is_down = is_down;
+                        Some(MouseButton::Left)
+                    },
+                    Button2 => {
+                        input.mouse_state.middle.is_down = is_down;
+                        Some(MouseButton::Middle)
+                    },
+                    Button3 => {
+                        input.mouse_state.right.is_down = is_down;
+                        Some(MouseButton::Right)
+                    },
+                    // The wheel comes in as presses of buttons 4 and 5
+                    Button4 if is_down => {
+                        input.mouse_state.wheel_delta = input.mouse_state.wheel_delta.saturating_add(WHEEL_STEP);
+                        input.events.push(time, InputEvent::Wheel(WHEEL_STEP));
+                        None
+                    },
+                    Button5 if is_down => {
+                        input.mouse_state.wheel_delta = input.mouse_state.wheel_delta.saturating_sub(WHEEL_STEP);
+                        input.events.push(time, InputEvent::Wheel(-WHEEL_STEP));
+                        None
+                    },
+                    _ => None
+                };
+
+                match mouse_button {
+                    Some(mouse_button) if is_down => input.events.push(time, InputEvent::MouseButtonDown(mouse_button)),
+                    Some(mouse_button) => input.events.push(time, InputEvent::MouseButtonUp(mouse_button)),
+                    None => {}
+                }
+            },
+            _ => {}
+        }
+    }
+
+    requests
+}
+
+// Switches relative mode on or off when the game asked for it, and while it's on
+// puts the pointer back in the middle of the window so it never stops at the screen edge
+unsafe fn update_relative_mouse(window: &X11Window, cursor: &mut CursorTracker, input: &InputController) {
+    let display = window.display;
+    let wants_relative = services::relative_mouse_requested();
+
+    if wants_relative && !cursor.is_relative {
+        let event_mask = (ButtonPressMask | ButtonReleaseMask | PointerMotionMask) as c_uint;
+        let result = XGrabPointer(
+            display, window.window, True, event_mask,
+            GrabModeAsync, GrabModeAsync, window.window, window.blank_cursor, CurrentTime);
+        // Someone else has the pointer, try again next frame
+        cursor.is_relative = result == GrabSuccess;
+    } else if !wants_relative && cursor.is_relative {
+        XUngrabPointer(display, CurrentTime);
+        let pos = input.mouse_state.pos;
+        XWarpPointer(display, 0, window.window, 0, 0, 0, 0, pos.x, pos.y);
+        cursor.last_pos = Some(pos);
+        cursor.is_relative = false;
+    }
+
+    if cursor.is_relative {
+        let dimensions = get_window_dimensions(window);
+        let center = Vector2i32 { x: dimensions.width as i32 / 2, y: dimensions.height as i32 / 2 };
+        if cursor.last_pos != Some(center) {
+            // The warp comes back as a motion event to the center, which then moves by nothing
+            XWarpPointer(display, 0, window.window, 0, 0, 0, 0, center.x, center.y);
+            cursor.last_pos = Some(center);
+        }
+    }
+
+    XFlush(display);
+}
+
+fn key_from_keysym(keysym: c_uint) -> Key {
+    match keysym {
+        // Unshifted keysyms, so letters are always lower case
+        XK_a..=XK_z => Key::letter(keysym - XK_a),
+        XK_0..=XK_9 => Key::digit(keysym - XK_0),
+        XK_F1..=XK_F12 => Key::function(keysym - XK_F1 + 1),
+        XK_Up => Key::Up,
+        XK_Left => Key::Left,
+        XK_Down => Key::Down,
+        XK_Right => Key::Right,
+        XK_Escape => Key::Escape,
+        XK_Tab => Key::Tab,
+        XK_space => Key::Space,
+        XK_Return => Key::Enter,
+        XK_BackSpace => Key::Backspace,
+        XK_Insert => Key::Insert,
+        XK_Delete => Key::Delete,
+        XK_Home => Key::Home,
+        XK_End => Key::End,
+        XK_Prior => Key::PageUp,
+        XK_Next => Key::PageDown,
+        XK_Shift_L => Key::LeftShift,
+        XK_Shift_R => Key::RightShift,
+        XK_Control_L => Key::LeftControl,
+        XK_Control_R => Key::RightControl,
+        XK_Alt_L => Key::LeftAlt,
+        XK_Alt_R | XK_ISO_Level3_Shift => Key::RightAlt,
+        XK_minus => Key::Minus,
+        XK_equal => Key::Equals,
+        XK_bracketleft => Key::LeftBracket,
+        XK_bracketright => Key::RightBracket,
+        XK_backslash => Key::Backslash,
+        XK_semicolon => Key::Semicolon,
+        XK_apostrophe => Key::Apostrophe,
+        XK_comma => Key::Comma,
+        XK_period => Key::Period,
+        XK_slash => Key::Slash,
+        XK_grave => Key::Grave,
+        _ => Key::Unknown
+    }
+}
+
+// XLookupString gives Latin-1, whose bytes are the first 256 code points
+unsafe fn push_text_events(key_event: &mut XKeyEvent, time: f64, input: &mut InputController) {
+    let mut text: [c_char; 32] = [0; 32];
+    let length = XLookupString(key_event, text.as_mut_ptr(), text.len() as c_int, null_mut(), null_mut());
+
+    for byte in &text[..length.max(0) as usize] {
+        let character = *byte as u8 as char;
+        if !character.is_control() {
+            input.events.push(time, InputEvent::Text(character));
+        }
+    }
+}
+
+unsafe fn resize_back_buffer(window: &X11Window, buffer: &mut X11OffscreenBuffer, width: u32, height: u32) {
+    destroy_back_buffer(buffer);
+
+    buffer.buffer.width = width;
+    buffer.buffer.height = height;
+    buffer.buffer.bytes_per_pixel = 4;
+    buffer.buffer.pixel_format = PixelFormat::Bgra8;
+    buffer.buffer.pitch = width * buffer.buffer.bytes_per_pixel;
+
+    buffer.pixels = vec![0; (width * height) as usize];
+    buffer.buffer.memory = buffer.pixels.as_mut_ptr() as *mut c_void;
+
+    // 32 bits per pixel in 24 bit depth is 0x00RRGGBB, same as Bgra8 without alpha
+    buffer.image = XCreateImage(
+        window.display,
+        window.visual,
+        window.depth as c_uint,
+        ZPixmap,
+        0,
+        buffer.buffer.memory as *mut c_char,
+        width,
+        height,
+        32,
+        buffer.buffer.pitch as c_int);
+}
+
+unsafe fn destroy_back_buffer(buffer: &mut X11OffscreenBuffer) {
+    if !buffer.image.is_null() {
+        // The pixels belong to the Vec, stop XDestroyImage from freeing them
+        (*buffer.image).data = null_mut();
+        XDestroyImage(buffer.image);
+        buffer.image = null_mut();
+    }
+}
+
+unsafe fn copy_buffer_to_window(window: &X11Window, buffer: &X11OffscreenBuffer) {
+    if buffer.image.is_null() {
+        return;
+    }
+
+    XPutImage(
+        window.display,
+        window.window,
+        window.gc,
+        buffer.image,
+        0, 0,
+        0, 0,
+        buffer.buffer.width,
+        buffer.buffer.height);
+
+    XFlush(window.display);
+}
+
+unsafe fn get_window_dimensions(window: &X11Window) -> WindowDimensions {
+    let mut attributes = MaybeUninit::<XWindowAttributes>::uninit();
+
+    if XGetWindowAttributes(window.display, window.window, attributes.as_mut_ptr()) == 0 {
+        return WindowDimensions { width: 0, height: 0 };
+    }
+
+    let attributes = attributes.assume_init();
+    WindowDimensions { width: attributes.width as u32, height: attributes.height as u32 }
+}
@@ -0,0 +1,179 @@
+use crate::oxide::*;
+use std::fs::{File, OpenOptions};
+use std::io::{ErrorKind, Read};
+use std::os::unix::fs::OpenOptionsExt;
+use std::time::{Duration, Instant};
+
+// From fcntl.h, the same on every architecture we build for
+const O_NONBLOCK: i32 = 0o4000;
+
+// struct js_event from linux/joystick.h
+const JS_EVENT_SIZE: usize = 8;
+const JS_EVENT_BUTTON: u8 = 0x01;
+const JS_EVENT_AXIS: u8 = 0x02;
+// Set on the made up events the driver sends right after opening, to report the current state
+const JS_EVENT_INIT: u8 = 0x80;
+
+// Looking for newly plugged in pads means trying to open files, so not every frame
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(1);
+
+// What the xpad driver reports for Xbox style pads
+const AXIS_LEFT_X: u8 = 0;
+const AXIS_LEFT_Y: u8 = 1;
+const AXIS_LEFT_TRIGGER: u8 = 2;
+const AXIS_RIGHT_X: u8 = 3;
+const AXIS_RIGHT_Y: u8 = 4;
+const AXIS_RIGHT_TRIGGER: u8 = 5;
+const AXIS_DPAD_X: u8 = 6;
+const AXIS_DPAD_Y: u8 = 7;
+
+const BUTTON_MAP: [Option<GamepadButton>; 11] = [
+    Some(GamepadButton::A),
+    Some(GamepadButton::B),
+    Some(GamepadButton::X),
+    Some(GamepadButton::Y),
+    Some(GamepadButton::LeftShoulder),
+    Some(GamepadButton::RightShoulder),
+    Some(GamepadButton::Back),
+    Some(GamepadButton::Start),
+    // Guide
+    None,
+    Some(GamepadButton::LeftThumb),
+    Some(GamepadButton::RightThumb)
+];
+
+// Raw axis values, kept since the driver only reports the ones that changed
+#[derive(Default)]
+struct JoystickAxes {
+    left_x: i16,
+    left_y: i16,
+    right_x: i16,
+    right_y: i16,
+    // Resting at -32767
+    left_trigger: i16,
+    right_trigger: i16
+}
+
+struct Joystick {
+    file: File,
+    axes: JoystickAxes
+}
+
+// Reads /dev/input/js0 to js3 through the joystick API, one per gamepad slot
+#[derive(Default)]
+pub struct LinuxGamepads {
+    joysticks: [Option<Joystick>; MAX_GAMEPADS],
+    last_reconnect_attempt: Option<Instant>
+}
+
+impl LinuxGamepads {
+    pub fn poll(&mut self, gamepads: &mut [GamepadState; MAX_GAMEPADS]) {
+        let reconnect = match self.last_reconnect_attempt {
+            Some(time) => time.elapsed() >= RECONNECT_INTERVAL,
+            None => true
+        };
+        if reconnect {
+            self.last_reconnect_attempt = Some(Instant::now());
+        }
+
+        let mut i = 0;
+        while i < MAX_GAMEPADS {
+            if self.joysticks[i].is_none() && reconnect {
+                self.joysticks[i] = open_joystick(i);
+                if self.joysticks[i].is_some() {
+                    gamepads[i] = GamepadState::default();
+                }
+            }
+
+            let connected = match &mut self.joysticks[i] {
+                Some(joystick) => read_joystick_events(joystick, &mut gamepads[i]),
+                None => false
+            };
+
+            if !connected {
+                self.joysticks[i] = None;
+                gamepads[i] = GamepadState::default();
+            }
+            gamepads[i].is_connected = connected;
+
+            i += 1;
+        }
+    }
+}
+
+fn open_joystick(index: usize) -> Option<Joystick> {
+    let file = OpenOptions::new()
+        .read(true)
+        .custom_flags(O_NONBLOCK)
+        .open(format!("/dev/input/js{}", index))
+        .ok()?;
+
+    Some(Joystick {
+        file,
+        axes: JoystickAxes {
+            left_trigger: -32767,
+            right_trigger: -32767,
+            ..Default::default()
+        }
+    })
+}
+
+// Returns false once the pad is gone
+fn read_joystick_events(joystick: &mut Joystick, gamepad: &mut GamepadState) -> bool {
+    let mut events = [0u8; JS_EVENT_SIZE * 64];
+
+    loop {
+        let size = match joystick.file.read(&mut events) {
+            Ok(0) => return false,
+            Ok(size) => size,
+            Err(error) if error.kind() == ErrorKind::WouldBlock => break,
+            Err(error) if error.kind() == ErrorKind::Interrupted => continue,
+            Err(_) => return false
+        };
+
+        for event in events[..size].chunks_exact(JS_EVENT_SIZE) {
+            let value = i16::from_le_bytes([event[4], event[5]]);
+            let event_type = event[6] & !JS_EVENT_INIT;
+            let number = event[7];
+
+            match event_type {
+                JS_EVENT_BUTTON => {
+                    if let Some(Some(button)) = BUTTON_MAP.get(number as usize) {
+                        gamepad.set_is_down(*button, value != 0);
+                    }
+                },
+                JS_EVENT_AXIS => match number {
+                    AXIS_LEFT_X => joystick.axes.left_x = value,
+                    AXIS_LEFT_Y => joystick.axes.left_y = value,
+                    AXIS_RIGHT_X => joystick.axes.right_x = value,
+                    AXIS_RIGHT_Y => joystick.axes.right_y = value,
+                    AXIS_LEFT_TRIGGER => joystick.axes.left_trigger = value,
+                    AXIS_RIGHT_TRIGGER => joystick.axes.right_trigger = value,
+                    AXIS_DPAD_X => {
+                        gamepad.set_is_down(GamepadButton::DpadLeft, value < 0);
+                        gamepad.set_is_down(GamepadButton::DpadRight, value > 0);
+                    },
+                    AXIS_DPAD_Y => {
+                        gamepad.set_is_down(GamepadButton::DpadUp, value < 0);
+                        gamepad.set_is_down(GamepadButton::DpadDown, value > 0);
+                    },
+                    _ => {}
+                },
+                _ => {}
+            }
+        }
+    }
+
+    let axes = &joystick.axes;
+    // The driver has y going down, GamepadState has it going up like XInput
+    gamepad.left_stick = apply_stick_deadzone(normalize_axis(axes.left_x), -normalize_axis(axes.left_y), LEFT_STICK_DEADZONE);
+    gamepad.right_stick = apply_stick_deadzone(normalize_axis(axes.right_x), -normalize_axis(axes.right_y), RIGHT_STICK_DEADZONE);
+    gamepad.left_trigger = apply_trigger_threshold((normalize_axis(axes.left_trigger) + 1.0) / 2.0);
+    gamepad.right_trigger = apply_trigger_threshold((normalize_axis(axes.right_trigger) + 1.0) / 2.0);
+
+    true
+}
+
+fn normalize_axis(value: i16) -> f32 {
+    (value as f32 / 32767.0).max(-1.0)
+}
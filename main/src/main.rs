@@ -1,31 +1,57 @@
 extern crate oxide;
 
-use std::mem;
-use std::fs;
-
+use game_code::GameCode;
+use std::env;
+use std::path::PathBuf;
+
+pub mod game_code;
+pub mod headless;
+#[cfg(target_os = "linux")]
+pub mod linux;
+#[cfg(target_os = "linux")]
+pub mod linux_gamepad;
+pub mod platform;
+pub mod recording;
+#[cfg(test)]
+mod reference_tests;
+pub mod services;
+#[cfg(windows)]
 pub mod win32;
 
-pub static mut LIBRARY: Option<libloading::Library> = None;
+pub fn main() {
+    let args: Vec<String> = env::args().collect();
 
-static LIB_PATH: &str = "../oxide/target/debug/";
+    if args.iter().any(|arg| arg == "--headless") {
+        headless::start_program(headless::HeadlessConfig::from_args(&args));
+    } else {
+        start_windowed(&args);
+    }
+}
 
-pub fn main() {
-    load_lib();
-    win32::start_program();
+#[cfg(windows)]
+fn start_windowed(args: &[String]) {
+    win32::start_program(GameCode::load(lib_path_from_args(args)), platform::LoopConfig::from_args(args));
 }
 
-pub fn reload_lib() {
-    unsafe { drop(mem::replace(&mut LIBRARY, None)); }
-    load_lib();
+#[cfg(target_os = "linux")]
+fn start_windowed(args: &[String]) {
+    linux::start_program(GameCode::load(lib_path_from_args(args)), platform::LoopConfig::from_args(args));
 }
 
-fn load_lib() {
-    fs::copy(format!("{}/oxide.dll", LIB_PATH), format!("{}/oxide_temp.dll", LIB_PATH)).expect("Unable to copy dll to temp");
+// No windowed backend for this OS yet, so run headless
+#[cfg(not(any(windows, target_os = "linux")))]
+fn start_windowed(args: &[String]) {
+    headless::start_program(headless::HeadlessConfig::from_args(args));
+}
 
-    unsafe {
-        LIBRARY = match libloading::Library::new(format!("{}/oxide_temp.dll", LIB_PATH)) {
-            Ok(value) => Some(value),
-            Err(error) => panic!("Unable to load oxide lib: {}", error)
-        };
+// --lib <path> picks the game library to load and watch for rebuilds
+#[cfg(any(windows, target_os = "linux"))]
+fn lib_path_from_args(args: &[String]) -> PathBuf {
+    match args.iter().position(|arg| arg == "--lib") {
+        Some(index) => match args.get(index + 1) {
+            Some(path) => PathBuf::from(path),
+            None => panic!("Missing value for --lib")
+        },
+        None => game_code::default_lib_path()
     }
 }
@@ -0,0 +1,318 @@
+use crate::oxide::*;
+use crate::game_code::GameCode;
+use crate::recording::InputRecorder;
+use crate::services::platform_services;
+use std::alloc::{alloc_zeroed, dealloc, Layout};
+use std::cmp::min;
+use std::ffi::c_void;
+use std::ptr::null_mut;
+
+// What the platform layer wants the game loop to do after pumping its messages
+#[derive(Default, Clone, Copy)]
+pub struct PlatformRequests {
+    pub quit: bool,
+    pub reload: bool,
+    // Start recording, or stop it and loop the playback, or stop the playback
+    pub toggle_recording: bool,
+    // Stops or restarts the updates, frames still get drawn while paused
+    pub toggle_pause: bool,
+    // Runs a single update while paused
+    pub single_step: bool,
+    // Halve or double how fast game time goes
+    pub slow_down: bool,
+    pub speed_up: bool
+}
+
+// The host's own keys, the same on every platform.
+// F5 reload, F6 pause, F7 single step, F8 slower, F9 faster, F10 recording.
+pub fn request_for_key(key: Key, requests: &mut PlatformRequests) {
+    match key {
+        Key::F5 => requests.reload = true,
+        Key::F6 => requests.toggle_pause = true,
+        Key::F7 => requests.single_step = true,
+        Key::F8 => requests.slow_down = true,
+        Key::F9 => requests.speed_up = true,
+        Key::F10 => requests.toggle_recording = true,
+        _ => {}
+    }
+}
+
+// How run_game_loop paces itself
+#[derive(Clone, Copy)]
+pub struct LoopConfig {
+    // Milliseconds of game time per update, the game always gets exactly this as delta_time
+    pub update_step: f64,
+    // Frames per second to sleep down to, None to draw as often as presenting allows
+    pub target_fps: Option<f64>
+}
+
+impl Default for LoopConfig {
+    fn default() -> Self {
+        LoopConfig {
+            update_step: 1000.0 / 60.0,
+            target_fps: Some(60.0)
+        }
+    }
+}
+
+impl LoopConfig {
+    // Reads --update-rate (updates per second) and --fps, where --fps 0 turns the cap off
+    pub fn from_args(args: &[String]) -> LoopConfig {
+        let mut config = LoopConfig::default();
+
+        if let Some(index) = args.iter().position(|arg| arg == "--update-rate") {
+            match args.get(index + 1).map(|value| value.parse::<f64>()) {
+                Some(Ok(rate)) if rate > 0.0 => config.update_step = 1000.0 / rate,
+                _ => panic!("Invalid or missing value for --update-rate")
+            }
+        }
+
+        if let Some(index) = args.iter().position(|arg| arg == "--fps") {
+            match args.get(index + 1).map(|value| value.parse::<f64>()) {
+                Some(Ok(fps)) if fps > 0.0 => config.target_fps = Some(fps),
+                Some(Ok(0.0)) => config.target_fps = None,
+                _ => panic!("Invalid or missing value for --fps")
+            }
+        }
+
+        config
+    }
+}
+
+// Turns the cursor positions a platform gets from the OS into MouseState::pos and delta.
+// In relative mode the platform keeps moving the real cursor back to the middle of the window,
+// pos then stays where it was when the mode started and only delta keeps going.
+#[derive(Default)]
+pub struct CursorTracker {
+    // Where the real cursor was last seen, None until the first motion message
+    pub last_pos: Option<Vector2i32>,
+    pub is_relative: bool
+}
+
+impl CursorTracker {
+    pub fn cursor_moved(&mut self, pos: Vector2i32, time: f64, input: &mut InputController) {
+        if let Some(last_pos) = self.last_pos {
+            input.mouse_state.delta.x += pos.x - last_pos.x;
+            input.mouse_state.delta.y += pos.y - last_pos.y;
+        }
+        self.last_pos = Some(pos);
+
+        if !self.is_relative {
+            input.mouse_state.pos = pos;
+            input.events.push(time, InputEvent::MouseMove(pos));
+        }
+    }
+}
+
+// Everything the game loop needs from an OS backend.
+// Implemented by win32, linux and headless so they all share run_game_loop.
+pub trait Platform {
+    // Fills in input with whatever happened since the last call
+    fn poll_input(&mut self, input: &mut InputController) -> PlatformRequests;
+    // The buffer the game draws into this frame
+    fn back_buffer(&mut self) -> &mut OffscreenBuffer;
+    // Shows the back buffer
+    fn present_buffer(&mut self);
+    fn window_size(&self) -> WindowDimensions;
+    // Milliseconds on a clock that never goes backwards
+    fn now(&self) -> f64;
+    fn sleep(&self, milliseconds: f64);
+    // None of the backends play sound yet
+    fn output_sound(&mut self, _sound_buffer: &SoundOutputBuffer) {}
+}
+
+const PERMANENT_STORAGE_SIZE: u64 = 64 * 1024 * 1024;
+const TRANSIENT_STORAGE_SIZE: u64 = 256 * 1024 * 1024;
+const STORAGE_ALIGNMENT: usize = 4096;
+
+const SOUND_SAMPLES_PER_SECOND: u32 = 48000;
+const SOUND_CHANNEL_COUNT: u32 = 2;
+// Longest stretch of sound asked for in one frame
+const SOUND_MAX_SAMPLE_COUNT: u32 = SOUND_SAMPLES_PER_SECOND / 10;
+
+// Longer frames (a breakpoint, dragging the window) are cut down to this, instead of
+// running so many updates to catch up that the next frame is even longer
+const MAX_FRAME_TIME: f64 = 250.0;
+// Clocks that tick in exact steps, like the headless one, can come up a rounding error short
+// of a whole update. Anything this close still counts as one.
+const UPDATE_STEP_TOLERANCE: f64 = 1e-6;
+// thread::sleep can overshoot by a millisecond or so, the last bit before a frame is spun instead
+const SLEEP_SPIN_TIME: f64 = 2.0;
+const MIN_TIME_SCALE: f64 = 1.0 / 16.0;
+const MAX_TIME_SCALE: f64 = 4.0;
+
+// Zeroed blocks for the game to lay its state out in, see GameMemory
+pub fn allocate_game_memory() -> GameMemory {
+    unsafe {
+        GameMemory {
+            is_initialized: false,
+            permanent_storage_size: PERMANENT_STORAGE_SIZE,
+            permanent_storage: alloc_zeroed(storage_layout(PERMANENT_STORAGE_SIZE)) as *mut c_void,
+            transient_storage_size: TRANSIENT_STORAGE_SIZE,
+            transient_storage: alloc_zeroed(storage_layout(TRANSIENT_STORAGE_SIZE)) as *mut c_void,
+            services: platform_services()
+        }
+    }
+}
+
+pub fn free_game_memory(memory: &mut GameMemory) {
+    unsafe {
+        if !memory.permanent_storage.is_null() {
+            dealloc(memory.permanent_storage as *mut u8, storage_layout(memory.permanent_storage_size));
+        }
+        if !memory.transient_storage.is_null() {
+            dealloc(memory.transient_storage as *mut u8, storage_layout(memory.transient_storage_size));
+        }
+    }
+
+    memory.permanent_storage = null_mut();
+    memory.transient_storage = null_mut();
+    memory.is_initialized = false;
+}
+
+fn storage_layout(size: u64) -> Layout {
+    Layout::from_size_align(size as usize, STORAGE_ALIGNMENT).expect("Invalid game memory size")
+}
+
+pub fn run_game_loop<P: Platform>(platform: &mut P, game_code: &mut GameCode, recorder: &mut InputRecorder, config: LoopConfig) {
+    let mut memory = allocate_game_memory();
+    if memory.permanent_storage.is_null() || memory.transient_storage.is_null() {
+        panic!("Unable to allocate game memory");
+    }
+
+    game_code.init(&mut memory);
+    recorder.start(&mut memory);
+
+    let mut input = InputController::default();
+    // What the platform has collected since the last update, there can be several frames
+    // between updates or several updates in one frame
+    let mut pending_input = InputController::default();
+    let mut sound_samples: Vec<i16> = vec![0; (SOUND_MAX_SAMPLE_COUNT * SOUND_CHANNEL_COUNT) as usize];
+
+    let update_step = config.update_step;
+    // Game time not yet covered by an update. The first frame gets one so there's something to draw.
+    let mut accumulator = update_step;
+    let mut paused = false;
+    let mut time_scale = 1.0;
+    let mut time_last_frame = platform.now();
+
+    loop {
+        let requests = platform.poll_input(&mut pending_input);
+
+        if requests.quit {
+            break;
+        }
+
+        if requests.reload {
+            println!("reload");
+            game_code.reload(&mut memory);
+        } else if game_code.reload_if_changed(&mut memory) {
+            println!("reload (library changed on disk)");
+        }
+
+        if requests.toggle_recording {
+            recorder.toggle(&mut memory);
+        }
+
+        if requests.toggle_pause {
+            paused = !paused;
+            println!("{}", if paused { "paused" } else { "unpaused" });
+        }
+
+        if requests.slow_down || requests.speed_up {
+            let factor = if requests.speed_up { 2.0 } else { 0.5 };
+            time_scale = f64::clamp(time_scale * factor, MIN_TIME_SCALE, MAX_TIME_SCALE);
+            println!("time scale {}", time_scale);
+        }
+
+        let current_time = platform.now();
+        let frame_time = f64::min(current_time - time_last_frame, MAX_FRAME_TIME);
+        time_last_frame = current_time;
+
+        let dimensions = platform.window_size();
+
+        // Nothing to draw into while minimized
+        if dimensions.width == 0 || dimensions.height == 0 {
+            clear_frame_input(&mut pending_input);
+            platform.sleep(10.0);
+            time_last_frame = platform.now();
+            continue;
+        }
+
+        let mut update_count = 0;
+        if paused {
+            accumulator = 0.0;
+            if requests.single_step {
+                update_count = 1;
+            }
+        } else {
+            accumulator += frame_time * time_scale;
+            while accumulator >= update_step * (1.0 - UPDATE_STEP_TOLERANCE) {
+                accumulator = f64::max(accumulator - update_step, 0.0);
+                update_count += 1;
+            }
+        }
+
+        let mut i = 0;
+        while i < update_count {
+            input.update(pending_input);
+            input.delta_time = update_step as f32;
+            clear_frame_input(&mut pending_input);
+
+            // The live input keeps tracking the devices while a recording plays over it
+            let mut update_input = input;
+            let mut update_dimensions = dimensions;
+            recorder.process_input(&mut memory, &mut update_input, &mut update_dimensions);
+
+            game_code.update(&mut memory, &mut update_input, &update_dimensions);
+            i += 1;
+        }
+
+        // Clicks and keys from while paused would all go off at once when unpausing
+        if paused && update_count == 0 {
+            clear_frame_input(&mut pending_input);
+        }
+
+        // Paused, the last update is as far as things go
+        let blend_factor = if paused { 1.0 } else { (accumulator / update_step) as f32 };
+        game_code.render(&mut memory, platform.back_buffer(), blend_factor);
+
+        platform.present_buffer();
+
+        // Enough sound to cover the last frame's worth of time
+        let mut sound_buffer = SoundOutputBuffer {
+            samples_per_second: SOUND_SAMPLES_PER_SECOND,
+            channel_count: SOUND_CHANNEL_COUNT,
+            sample_count: min((SOUND_SAMPLES_PER_SECOND as f64 * frame_time / 1000.0) as u32, SOUND_MAX_SAMPLE_COUNT),
+            samples: sound_samples.as_mut_ptr()
+        };
+        game_code.get_sound_samples(&mut memory, &mut sound_buffer);
+        platform.output_sound(&sound_buffer);
+
+        if let Some(target_fps) = config.target_fps {
+            sleep_until(platform, current_time + 1000.0 / target_fps);
+        }
+    }
+
+    game_code.shutdown(&mut memory);
+    free_game_memory(&mut memory);
+}
+
+// Drops what only counts for one update, button and key states stay as they are
+fn clear_frame_input(input: &mut InputController) {
+    input.events.clear();
+    // The platforms add up every wheel and motion message on top of these
+    input.mouse_state.wheel_delta = 0;
+    input.mouse_state.delta = Vector2i32::default();
+}
+
+fn sleep_until<P: Platform>(platform: &P, time: f64) {
+    let remaining = time - platform.now();
+    if remaining > SLEEP_SPIN_TIME {
+        platform.sleep(remaining - SLEEP_SPIN_TIME);
+    }
+
+    while platform.now() < time {
+        std::hint::spin_loop();
+    }
+}
@@ -0,0 +1,302 @@
+use crate::oxide::*;
+use std::fs;
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::slice;
+
+// "OXRC", then a version for when the format itself changes
+const RECORDING_MAGIC: u32 = 0x4352584F;
+const RECORDING_VERSION: u32 = 2;
+
+// Where the windowed hosts save what they record
+pub const DEFAULT_RECORDING_PATH: &str = "oxide_input.rec";
+
+// magic, version, layout fingerprint hash, InputController size, snapshot size
+const HEADER_SIZE: usize = 4 + 4 + 8 + 4 + 8;
+// window width and height, then the InputController
+const FRAME_SIZE: usize = 4 + 4 + size_of::<InputController>();
+
+// What the game got for one update. The window size goes along with the input since the camera
+// and where the mouse points in the world both depend on it.
+#[derive(Clone, Copy)]
+struct RecordedFrame {
+    screen: WindowDimensions,
+    input: InputController
+}
+
+// What headless --replay needs to know before the game loop starts
+pub struct RecordingSummary {
+    pub frame_count: usize,
+    // Of the first frame, None when there are no frames
+    pub window_size: Option<WindowDimensions>
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RecordingState {
+    Idle,
+    Recording,
+    PlayingBack
+}
+
+// Records the InputController of every frame, delta_time included, starting from a snapshot
+// of permanent storage, then plays it back in a loop by restoring the snapshot every time it ends.
+// That way a glitch can be replayed over and over while hot reloading fixes for it.
+//
+// File layout, little endian:
+//   header (see HEADER_SIZE)
+//   permanent storage snapshot, trailing zeroes trimmed off
+//   per frame the window width and height, then a raw InputController
+pub struct InputRecorder {
+    path: PathBuf,
+    state: RecordingState,
+    snapshot: Vec<u8>,
+    frames: Vec<RecordedFrame>,
+    playback_index: usize,
+    // Replays the file as soon as the game loop starts, used by headless --replay
+    play_file_on_start: bool
+}
+
+impl InputRecorder {
+    pub fn new(path: PathBuf) -> InputRecorder {
+        InputRecorder {
+            path,
+            state: RecordingState::Idle,
+            snapshot: Vec::new(),
+            frames: Vec::new(),
+            playback_index: 0,
+            play_file_on_start: false
+        }
+    }
+
+    pub fn replaying(path: PathBuf) -> InputRecorder {
+        let mut recorder = InputRecorder::new(path);
+        recorder.play_file_on_start = true;
+        recorder
+    }
+
+    // Called once the game has initialized its memory
+    pub fn start(&mut self, memory: &mut GameMemory) {
+        if !self.play_file_on_start {
+            return;
+        }
+
+        match self.load_file() {
+            Ok(()) => self.begin_playback(memory),
+            Err(error) => eprintln!("Unable to replay {}: {}", self.path.display(), error)
+        }
+    }
+
+    // Idle -> recording -> playing back -> idle
+    pub fn toggle(&mut self, memory: &mut GameMemory) {
+        match self.state {
+            RecordingState::Idle => {
+                println!("Recording input to {}", self.path.display());
+                self.snapshot = snapshot_permanent_storage(memory);
+                self.frames.clear();
+                self.state = RecordingState::Recording;
+            },
+            RecordingState::Recording => {
+                println!("Recorded {} frames, playing them back", self.frames.len());
+                if let Err(error) = self.save_file() {
+                    eprintln!("Unable to save recording to {}: {}", self.path.display(), error);
+                }
+                self.begin_playback(memory);
+            },
+            RecordingState::PlayingBack => {
+                println!("Stopped playback");
+                self.state = RecordingState::Idle;
+            }
+        }
+    }
+
+    // Records the input and window size or swaps them for the recorded ones, depending on the state
+    pub fn process_input(&mut self, memory: &mut GameMemory, input: &mut InputController, screen: &mut WindowDimensions) {
+        match self.state {
+            RecordingState::Idle => {},
+            RecordingState::Recording => {
+                self.frames.push(RecordedFrame { screen: *screen, input: *input });
+            },
+            RecordingState::PlayingBack => {
+                if self.playback_index >= self.frames.len() {
+                    self.playback_index = 0;
+                    restore_permanent_storage(memory, &self.snapshot);
+                }
+
+                let frame = self.frames[self.playback_index];
+                *input = frame.input;
+                *screen = frame.screen;
+                self.playback_index += 1;
+            }
+        }
+    }
+
+    fn begin_playback(&mut self, memory: &mut GameMemory) {
+        if self.frames.is_empty() {
+            println!("Nothing recorded, not playing back");
+            self.state = RecordingState::Idle;
+            return;
+        }
+
+        restore_permanent_storage(memory, &self.snapshot);
+        self.playback_index = 0;
+        self.state = RecordingState::PlayingBack;
+    }
+
+    fn save_file(&self) -> Result<(), String> {
+        let mut bytes: Vec<u8> = Vec::with_capacity(HEADER_SIZE + self.snapshot.len() + self.frames.len() * FRAME_SIZE);
+
+        bytes.extend_from_slice(&RECORDING_MAGIC.to_le_bytes());
+        bytes.extend_from_slice(&RECORDING_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&layout_fingerprint().field_hash.to_le_bytes());
+        bytes.extend_from_slice(&(size_of::<InputController>() as u32).to_le_bytes());
+        bytes.extend_from_slice(&(self.snapshot.len() as u64).to_le_bytes());
+        bytes.extend_from_slice(&self.snapshot);
+
+        for frame in &self.frames {
+            bytes.extend_from_slice(&frame.screen.width.to_le_bytes());
+            bytes.extend_from_slice(&frame.screen.height.to_le_bytes());
+            let input_bytes = unsafe { slice::from_raw_parts(&frame.input as *const InputController as *const u8, size_of::<InputController>()) };
+            bytes.extend_from_slice(input_bytes);
+        }
+
+        fs::write(&self.path, bytes).map_err(|error| error.to_string())
+    }
+
+    fn load_file(&mut self) -> Result<(), String> {
+        let bytes = fs::read(&self.path).map_err(|error| error.to_string())?;
+        let (snapshot_size, frame_count) = read_header(&bytes)?;
+
+        self.snapshot = bytes[HEADER_SIZE..HEADER_SIZE + snapshot_size].to_vec();
+        self.frames.clear();
+
+        let mut i = 0;
+        while i < frame_count {
+            self.frames.push(read_frame(&bytes, snapshot_size, i));
+            i += 1;
+        }
+
+        Ok(())
+    }
+}
+
+// None if it isn't a recording we can play
+pub fn read_recording_summary(path: &Path) -> Option<RecordingSummary> {
+    let bytes = fs::read(path).ok()?;
+    let (snapshot_size, frame_count) = read_header(&bytes).ok()?;
+
+    Some(RecordingSummary {
+        frame_count,
+        window_size: if frame_count > 0 { Some(read_frame(&bytes, snapshot_size, 0).screen) } else { None }
+    })
+}
+
+// read_header has to have checked the file first
+fn read_frame(bytes: &[u8], snapshot_size: usize, index: usize) -> RecordedFrame {
+    let offset = HEADER_SIZE + snapshot_size + index * FRAME_SIZE;
+    let read_u32 = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+
+    RecordedFrame {
+        screen: WindowDimensions { width: read_u32(offset), height: read_u32(offset + 4) },
+        // The fingerprint check makes sure these bytes are an InputController from this same layout
+        input: unsafe { ptr::read_unaligned(bytes[offset + 8..].as_ptr() as *const InputController) }
+    }
+}
+
+// Returns the snapshot size and frame count
+fn read_header(bytes: &[u8]) -> Result<(usize, usize), String> {
+    if bytes.len() < HEADER_SIZE {
+        return Err(String::from("File is too short to be a recording"));
+    }
+
+    let read_u32 = |offset: usize| u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap());
+    let read_u64 = |offset: usize| u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap());
+
+    if read_u32(0) != RECORDING_MAGIC {
+        return Err(String::from("Not a recording"));
+    }
+    if read_u32(4) != RECORDING_VERSION {
+        return Err(format!("Recording format version {} isn't supported", read_u32(4)));
+    }
+    if read_u64(8) != layout_fingerprint().field_hash || read_u32(16) as usize != size_of::<InputController>() {
+        return Err(String::from("Recording was made by a build with a different input layout"));
+    }
+
+    let snapshot_size = read_u64(20) as usize;
+    if HEADER_SIZE + snapshot_size > bytes.len() {
+        return Err(String::from("Recording is cut off in the snapshot"));
+    }
+
+    let frame_count = (bytes.len() - HEADER_SIZE - snapshot_size) / FRAME_SIZE;
+    Ok((snapshot_size, frame_count))
+}
+
+// Trailing zeroes are left out since storage starts zeroed, which keeps the file small
+fn snapshot_permanent_storage(memory: &GameMemory) -> Vec<u8> {
+    let storage = unsafe { slice::from_raw_parts(memory.permanent_storage as *const u8, memory.permanent_storage_size as usize) };
+
+    let used_size = match storage.iter().rposition(|byte| *byte != 0) {
+        Some(index) => index + 1,
+        None => 0
+    };
+
+    storage[..used_size].to_vec()
+}
+
+fn restore_permanent_storage(memory: &mut GameMemory, snapshot: &[u8]) {
+    let storage = unsafe { slice::from_raw_parts_mut(memory.permanent_storage as *mut u8, memory.permanent_storage_size as usize) };
+
+    storage[..snapshot.len()].copy_from_slice(snapshot);
+    storage[snapshot.len()..].fill(0);
+
+    // An empty snapshot was taken before the game laid anything out
+    memory.is_initialized = !snapshot.is_empty();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::platform_services;
+    use std::ffi::c_void;
+
+    #[test]
+    fn playback_brings_back_the_recorded_window_size() {
+        let path = std::env::temp_dir().join(format!("oxide_recording_test_{}.rec", std::process::id()));
+        let mut permanent = vec![0u8; 4096];
+        let mut memory = GameMemory {
+            is_initialized: true,
+            permanent_storage_size: permanent.len() as u64,
+            permanent_storage: permanent.as_mut_ptr() as *mut c_void,
+            transient_storage_size: 0,
+            transient_storage: ptr::null_mut(),
+            services: platform_services()
+        };
+        permanent[0] = 1;
+
+        let mut recorder = InputRecorder::new(path.clone());
+        recorder.toggle(&mut memory);
+
+        let mut input = InputController { delta_time: 5.0, ..Default::default() };
+        let mut screen = WindowDimensions { width: 640, height: 360 };
+        recorder.process_input(&mut memory, &mut input, &mut screen);
+
+        // Saves the file and starts playing it back
+        recorder.toggle(&mut memory);
+
+        let summary = read_recording_summary(&path).unwrap();
+        let window_size = summary.window_size.unwrap();
+        assert_eq!(summary.frame_count, 1);
+        assert_eq!((window_size.width, window_size.height), (640, 360));
+
+        let mut replayer = InputRecorder::replaying(path.clone());
+        replayer.start(&mut memory);
+
+        let mut input = InputController::default();
+        let mut screen = WindowDimensions { width: 1280, height: 720 };
+        replayer.process_input(&mut memory, &mut input, &mut screen);
+        assert_eq!((screen.width, screen.height), (640, 360));
+        assert_eq!(input.delta_time, 5.0);
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,56 @@
+use crate::oxide::*;
+use crate::headless::*;
+use std::env;
+use std::path::PathBuf;
+
+// Renders each of oxide's test patterns and the headless scene and checks them against main/reference,
+// within what compare_ppm allows for rounding. After a change to rendering that's meant to show, run the tests with
+// UPDATE_REFERENCE_IMAGES=1 to write new references, and look them over before committing them.
+
+const PATTERN_SIZE: u32 = 128;
+
+#[test]
+fn test_patterns_match_references() {
+    for name in TEST_PATTERN_NAMES {
+        let mut pixels: Vec<u32> = vec![0; (PATTERN_SIZE * PATTERN_SIZE) as usize];
+        let mut canvas = Canvas::new(&mut pixels, PATTERN_SIZE, PATTERN_SIZE, PATTERN_SIZE as usize);
+        assert!(draw_test_pattern(name, &mut canvas), "No test pattern called {}", name);
+
+        let output_path = output_path(name);
+        write_ppm(&output_path, &pixels, PATTERN_SIZE, PATTERN_SIZE).expect("Unable to write pattern");
+        check_against_reference(&output_path, name);
+    }
+}
+
+#[test]
+fn scene_matches_reference() {
+    let output_path = output_path("scene");
+    run_game(HeadlessConfig {
+        frames: 3,
+        width: 320,
+        height: 180,
+        output_path: output_path.clone(),
+        ..Default::default()
+    }).expect("Unable to run the scene");
+
+    check_against_reference(&output_path, "scene");
+}
+
+// Somewhere each test can write without getting in another's way
+fn output_path(name: &str) -> String {
+    env::temp_dir().join(format!("oxide_{}_{}.ppm", name, std::process::id())).to_string_lossy().into_owned()
+}
+
+fn check_against_reference(output_path: &str, name: &str) {
+    let reference_path = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("reference").join(format!("{}.ppm", name));
+    let reference_path = reference_path.to_string_lossy().into_owned();
+
+    if env::var_os("UPDATE_REFERENCE_IMAGES").is_some() {
+        std::fs::copy(output_path, &reference_path).expect("Unable to update reference image");
+    } else {
+        let difference_count = compare_ppm(output_path, &reference_path);
+        assert!(difference_count <= MAX_DIFFERING_PIXELS, "{} differs from {} in {} pixels", output_path, reference_path, difference_count);
+    }
+
+    let _ = std::fs::remove_file(output_path);
+}
@@ -0,0 +1,95 @@
+use crate::oxide::*;
+use std::fs;
+use std::slice;
+use std::str;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::Instant;
+
+static START_TIME: OnceLock<Instant> = OnceLock::new();
+// Set by the game, the platform layer picks it up when it polls input
+static RELATIVE_MOUSE: AtomicBool = AtomicBool::new(false);
+
+// The PlatformServices every host hands to the game
+pub fn platform_services() -> PlatformServices {
+    START_TIME.get_or_init(Instant::now);
+
+    PlatformServices {
+        get_file_size,
+        read_entire_file,
+        write_entire_file,
+        log,
+        now,
+        set_relative_mouse
+    }
+}
+
+unsafe fn str_from_parts<'a>(pointer: *const u8, length: usize) -> &'a str {
+    str::from_utf8(slice::from_raw_parts(pointer, length)).unwrap_or("<invalid utf-8>")
+}
+
+extern "C" fn get_file_size(path: *const u8, path_length: usize) -> i64 {
+    let path = unsafe { str_from_parts(path, path_length) };
+
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.is_file() => metadata.len() as i64,
+        _ => -1
+    }
+}
+
+extern "C" fn read_entire_file(path: *const u8, path_length: usize, destination: *mut u8, destination_size: u64) -> bool {
+    let path = unsafe { str_from_parts(path, path_length) };
+
+    match fs::read(path) {
+        Ok(contents) => {
+            if contents.len() as u64 != destination_size {
+                eprintln!("[error] {} changed size while it was being read", path);
+                return false;
+            }
+
+            unsafe { slice::from_raw_parts_mut(destination, contents.len()).copy_from_slice(&contents); }
+            true
+        },
+        Err(error) => {
+            eprintln!("[error] Unable to read {}: {}", path, error);
+            false
+        }
+    }
+}
+
+extern "C" fn write_entire_file(path: *const u8, path_length: usize, data: *const u8, data_size: u64) -> bool {
+    let path = unsafe { str_from_parts(path, path_length) };
+    let data = unsafe { slice::from_raw_parts(data, data_size as usize) };
+
+    match fs::write(path, data) {
+        Ok(_) => true,
+        Err(error) => {
+            eprintln!("[error] Unable to write {}: {}", path, error);
+            false
+        }
+    }
+}
+
+extern "C" fn log(level: LogLevel, message: *const u8, message_length: usize) {
+    let message = unsafe { str_from_parts(message, message_length) };
+
+    match level {
+        LogLevel::Debug => println!("[debug] {}", message),
+        LogLevel::Info => println!("[info] {}", message),
+        LogLevel::Warning => eprintln!("[warning] {}", message),
+        LogLevel::Error => eprintln!("[error] {}", message)
+    }
+}
+
+// Also what the platform layers stamp input events with
+pub extern "C" fn now() -> f64 {
+    START_TIME.get_or_init(Instant::now).elapsed().as_secs_f64() * 1000.0
+}
+
+extern "C" fn set_relative_mouse(enabled: bool) {
+    RELATIVE_MOUSE.store(enabled, Ordering::Relaxed);
+}
+
+pub fn relative_mouse_requested() -> bool {
+    RELATIVE_MOUSE.load(Ordering::Relaxed)
+}
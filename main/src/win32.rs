@@ -1,101 +1,259 @@
 use crate::oxide::*;
-use crate::LIBRARY;
-use std::time::SystemTime;
-use std::time::UNIX_EPOCH;
+use crate::game_code::GameCode;
+use crate::platform::*;
+use crate::recording::{InputRecorder, DEFAULT_RECORDING_PATH};
+use crate::services;
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+use std::time::Instant;
 use std::ffi::c_void;
 use std::mem::size_of;
+use std::panic;
 use std::ptr::null_mut;
+use std::sync::{Mutex, MutexGuard, OnceLock};
 use windows::core::*;
 use windows::Win32::Foundation::*;
 use windows::Win32::Graphics::Gdi::*;
 use windows::Win32::System::LibraryLoader::GetModuleHandleA;
+use windows::Win32::Media::{timeBeginPeriod, timeEndPeriod};
 use windows::Win32::System::Memory::*;
 use windows::Win32::UI::WindowsAndMessaging::*;
 use windows::Win32::UI::Input::KeyboardAndMouse::*;
+use windows::Win32::UI::Input::XboxController::*;
+
+const GAMEPAD_SCAN_INTERVAL: Duration = Duration::from_secs(1);
+
+const XINPUT_BUTTON_MAP: [(GamepadButton, XINPUT_GAMEPAD_BUTTON_FLAGS); GAMEPAD_BUTTON_COUNT] = [
+    (GamepadButton::A, XINPUT_GAMEPAD_A),
+    (GamepadButton::B, XINPUT_GAMEPAD_B),
+    (GamepadButton::X, XINPUT_GAMEPAD_X),
+    (GamepadButton::Y, XINPUT_GAMEPAD_Y),
+    (GamepadButton::LeftShoulder, XINPUT_GAMEPAD_LEFT_SHOULDER),
+    (GamepadButton::RightShoulder, XINPUT_GAMEPAD_RIGHT_SHOULDER),
+    (GamepadButton::Back, XINPUT_GAMEPAD_BACK),
+    (GamepadButton::Start, XINPUT_GAMEPAD_START),
+    (GamepadButton::LeftThumb, XINPUT_GAMEPAD_LEFT_THUMB),
+    (GamepadButton::RightThumb, XINPUT_GAMEPAD_RIGHT_THUMB),
+    (GamepadButton::DpadUp, XINPUT_GAMEPAD_DPAD_UP),
+    (GamepadButton::DpadDown, XINPUT_GAMEPAD_DPAD_DOWN),
+    (GamepadButton::DpadLeft, XINPUT_GAMEPAD_DPAD_LEFT),
+    (GamepadButton::DpadRight, XINPUT_GAMEPAD_DPAD_RIGHT)
+];
+
+// Sent to the window by the game thread
+const WM_UPDATE_RELATIVE_MOUSE: u32 = WM_APP;
+// The game loop has ended, time to close the window and leave the message loop
+const WM_GAME_FINISHED: u32 = WM_APP + 1;
+
+// What the window thread and the game thread share. wnd_proc fills in the input and the
+// game thread takes it from there, the game thread hands over finished frames for WM_PAINT.
+struct WindowShared {
+    // Whatever came in since the game thread last polled
+    input: InputController,
+    requests: PlatformRequests,
+    cursor: CursorTracker,
+    // WM_CHAR hands over UTF-16, characters past the BMP come as two messages in a row
+    high_surrogate: Option<u16>,
+    // The last frame the game finished, the other half of Win32Platform::back_buffer
+    front_buffer: Win32OffscreenBuffer
+}
 
-static mut GAME_UPDATE_AND_RENDER: Option<libloading::Symbol<unsafe extern fn(game_state: &mut GameState, input_controller: &mut InputController, buffer: &mut OffscreenBuffer) -> ()>> = None;
-static mut IS_RUNNING: bool = true;
-// TODO: Figure out how to do this without typing everything out
-// default does not work on statics!
-static mut BACK_BUFFER: OffscreenBuffer = OffscreenBuffer {
-    info: BITMAPINFO {
-        bmiHeader: BITMAPINFOHEADER {
-            biSize: 0,
-            biWidth: 0,
-            biHeight: 0,
-            biPlanes: 0,
-            biBitCount: 0,
-            biCompression: 0,
-            biSizeImage: 0,
-            biXPelsPerMeter: 0,
-            biYPelsPerMeter: 0,
-            biClrUsed: 0,
-            biClrImportant: 0
-        },
-        bmiColors: [RGBQUAD {
-            rgbBlue: 0,
-            rgbGreen: 0,
-            rgbRed: 0,
-            rgbReserved: 0
-        }]
-    },
-    memory: null_mut(),
-    width: 0,
-    height: 0,
-    bytes_per_pixel: 0,
-    pitch: 0,
-};
-
-pub fn start_program() {
-    unsafe {
-        let window: HWND = create_window().unwrap();
+static WINDOW_SHARED: OnceLock<Mutex<WindowShared>> = OnceLock::new();
 
-        let window_size = get_window_dimensions(window);
+fn window_shared() -> MutexGuard<'static, WindowShared> {
+    WINDOW_SHARED.get().expect("Window state isn't set up").lock().expect("Window state lock poisoned")
+}
 
-        resize_dib_section(&mut BACK_BUFFER, window_size.width, window_size.height)
-            .expect("Unable to resize DIB section");
+// The GDI header only matters to StretchDIBits, the game just sees the buffer
+struct Win32OffscreenBuffer {
+    info: BITMAPINFO,
+    buffer: OffscreenBuffer
+}
 
-        let device_context: HDC = GetDC(window);
+// Only ever used by one thread at a time, the front buffer is behind WINDOW_SHARED's lock
+unsafe impl Send for Win32OffscreenBuffer {}
+
+impl Win32OffscreenBuffer {
+    fn empty() -> Win32OffscreenBuffer {
+        Win32OffscreenBuffer {
+            info: BITMAPINFO::default(),
+            buffer: OffscreenBuffer {
+                memory: null_mut(),
+                width: 0,
+                height: 0,
+                pitch: 0,
+                bytes_per_pixel: 0,
+                pixel_format: PixelFormat::Bgra8
+            }
+        }
+    }
+}
 
-        let mut game_state = GameState::default();
+// Lives on the game thread. The window and its messages stay on the thread that made them.
+pub struct Win32Platform {
+    window: HWND,
+    device_context: HDC,
+    // What the game draws into, swapped with the front buffer on present
+    back_buffer: Win32OffscreenBuffer,
+    last_gamepad_scan: Option<Instant>
+}
 
-        game_state.camera = Camera::new(0.0, 0.0, 16.0, 9.0);
-        game_state.curves[0] = Some(BezierCurve::new(
-            Vector2 { x: 0.0, y: 0.5 },
-            Vector2 { x: 1.0, y: 0.0 },
-            Vector2 { x: 1.0, y: 1.6 },
-            Vector2 { x: 0.0, y: 2.0 }
-        ));
+impl Platform for Win32Platform {
+    fn poll_input(&mut self, input: &mut InputController) -> PlatformRequests {
+        // XInputGetState stalls for a while on empty slots, so those are only checked now and then
+        let scan_disconnected = match self.last_gamepad_scan {
+            Some(time) => time.elapsed() >= GAMEPAD_SCAN_INTERVAL,
+            None => true
+        };
+        if scan_disconnected {
+            self.last_gamepad_scan = Some(Instant::now());
+        }
 
-        let mut time_last_frame: f64 = 0.0;
+        unsafe {
+            poll_gamepads(&mut input.gamepads, scan_disconnected);
+            let (requests, is_relative) = take_window_input(input);
 
-        let mut input = InputController::default();
+            // Only the window's own thread can hide its cursor
+            if is_relative || services::relative_mouse_requested() {
+                let _ = PostMessageA(self.window, WM_UPDATE_RELATIVE_MOUSE, WPARAM(0), LPARAM(0));
+            }
 
-        while IS_RUNNING {
-            let mut new_input = input;
-            process_pending_messages(&mut new_input);
+            // The window may have been resized since, nobody else touches the back buffer
+            let dimensions = get_window_dimensions(self.window);
+            let buffer = &self.back_buffer.buffer;
+            if dimensions.width != 0 && dimensions.height != 0
+                && (dimensions.width != buffer.width || dimensions.height != buffer.height) {
+                resize_dib_section(&mut self.back_buffer, dimensions.width, dimensions.height)
+                    .expect("Unable to resize DIB section");
+            }
 
-            input.update(new_input);
+            requests
+        }
+    }
 
-            let dimensions = get_window_dimensions(window);
+    fn back_buffer(&mut self) -> &mut OffscreenBuffer {
+        &mut self.back_buffer.buffer
+    }
 
-            game_state.camera.y_scale = dimensions.height as f32 / game_state.camera.height;
-            game_state.camera.width = dimensions.width as f32 / game_state.camera.y_scale;
+    fn present_buffer(&mut self) {
+        let mut shared = window_shared();
+        std::mem::swap(&mut self.back_buffer, &mut shared.front_buffer);
 
-            game_update_and_render(&mut game_state, &mut input, &mut BACK_BUFFER);
+        unsafe {
+            let dimensions = get_window_dimensions(self.window);
 
             copy_buffer_to_window(
-                &mut BACK_BUFFER,
-                device_context,
+                &shared.front_buffer,
+                self.device_context,
                 dimensions.width,
                 dimensions.height)
                 .expect("Unable to update window");
+        }
+    }
+
+    fn window_size(&self) -> WindowDimensions {
+        unsafe { get_window_dimensions(self.window) }
+    }
+
+    fn now(&self) -> f64 {
+        services::now()
+    }
+
+    fn sleep(&self, milliseconds: f64) {
+        thread::sleep(Duration::from_secs_f64(milliseconds / 1000.0));
+    }
+}
 
-            let start = SystemTime::now();
-            let current_time = start.duration_since(UNIX_EPOCH).expect("Time went backwards").as_micros() as f64 / 1000.0;
-            game_state.delta_time = (current_time - time_last_frame) as f32;
-            time_last_frame = current_time;
+// Moves everything wnd_proc collected over to input. Also says whether the cursor is in relative mode.
+fn take_window_input(input: &mut InputController) -> (PlatformRequests, bool) {
+    let mut shared = window_shared();
+    let window_input = &mut shared.input;
+
+    for event in window_input.events.iter() {
+        input.events.push(event.time, event.event);
+    }
+
+    let mouse = &window_input.mouse_state;
+    input.mouse_state.pos = mouse.pos;
+    input.mouse_state.delta.x += mouse.delta.x;
+    input.mouse_state.delta.y += mouse.delta.y;
+    input.mouse_state.wheel_delta = input.mouse_state.wheel_delta.saturating_add(mouse.wheel_delta);
+    input.mouse_state.left.is_down = mouse.left.is_down;
+    input.mouse_state.right.is_down = mouse.right.is_down;
+    input.mouse_state.middle.is_down = mouse.middle.is_down;
+    input.keyboard = window_input.keyboard;
+
+    window_input.events.clear();
+    window_input.mouse_state.delta = Vector2i32::default();
+    window_input.mouse_state.wheel_delta = 0;
+
+    (std::mem::take(&mut shared.requests), shared.cursor.is_relative)
+}
+
+// The game runs on its own thread so this one can stay in the message loop,
+// which Windows takes over for as long as the window is being dragged or resized
+pub fn start_program(game_code: GameCode, loop_config: LoopConfig) {
+    WINDOW_SHARED.get_or_init(|| Mutex::new(WindowShared {
+        input: InputController::default(),
+        requests: PlatformRequests::default(),
+        cursor: CursorTracker::default(),
+        high_surrogate: None,
+        front_buffer: Win32OffscreenBuffer::empty()
+    }));
+
+    unsafe {
+        let window: HWND = create_window().unwrap();
+
+        let game_thread = thread::spawn(move || run_game_thread(window, game_code, loop_config));
+
+        let mut message = MSG::default();
+        while GetMessageA(&mut message, None, 0, 0).0 > 0 {
+            TranslateMessage(&message);
+            DispatchMessageA(&message);
         }
+
+        if let Err(panic) = game_thread.join() {
+            panic::resume_unwind(panic);
+        }
+    }
+}
+
+// Tells the window thread to wrap up when the game thread is done, panicking or not
+struct FinishOnDrop(HWND);
+
+impl Drop for FinishOnDrop {
+    fn drop(&mut self) {
+        unsafe { let _ = PostMessageA(self.0, WM_GAME_FINISHED, WPARAM(0), LPARAM(0)); }
+    }
+}
+
+fn run_game_thread(window: HWND, mut game_code: GameCode, loop_config: LoopConfig) {
+    let _finish = FinishOnDrop(window);
+
+    unsafe {
+        let device_context: HDC = GetDC(window);
+
+        let mut platform = Win32Platform {
+            window,
+            device_context,
+            back_buffer: Win32OffscreenBuffer::empty(),
+            last_gamepad_scan: None
+        };
+
+        let window_size = get_window_dimensions(window);
+        resize_dib_section(&mut platform.back_buffer, window_size.width, window_size.height)
+            .expect("Unable to resize DIB section");
+
+        let mut recorder = InputRecorder::new(PathBuf::from(DEFAULT_RECORDING_PATH));
+
+        // Sleep rounds up to the scheduler period, 15.6ms unless asked for finer
+        timeBeginPeriod(1);
+        run_game_loop(&mut platform, &mut game_code, &mut recorder, loop_config);
+        timeEndPeriod(1);
+
+        free_dib_section(&mut platform.back_buffer);
+        ReleaseDC(window, device_context);
     }
 }
 
@@ -136,17 +294,20 @@ extern "system" fn wnd_proc(window: HWND, message: u32, w_param: WPARAM, l_param
     unsafe {
         match message {
             WM_PAINT => {
-                println!("WM_PAINT");
-
                 let mut paint: PAINTSTRUCT = PAINTSTRUCT::default();
                 let hdc = BeginPaint(window, &mut paint);
 
                 let dimensions: WindowDimensions = get_window_dimensions(window);
 
-                copy_buffer_to_window(&mut BACK_BUFFER, hdc, dimensions.width, dimensions.height)
-                    .expect("Unable to update window");
+                // Whatever the game thread finished last, it keeps drawing while the window is resized
+                let shared = window_shared();
+                if !shared.front_buffer.buffer.memory.is_null() {
+                    copy_buffer_to_window(&shared.front_buffer, hdc, dimensions.width, dimensions.height)
+                        .expect("Unable to update window");
+                }
+                drop(shared);
 
-                EndPaint(window, &mut paint);
+                EndPaint(window, &paint);
 
                 LRESULT(0)
             }
@@ -155,18 +316,25 @@ extern "system" fn wnd_proc(window: HWND, message: u32, w_param: WPARAM, l_param
                 PostQuitMessage(0);
                 LRESULT(0)
             }
+            // The game thread gets to finish its frame and shut down, it closes the window after
             WM_CLOSE => {
                 println!("WM_CLOSE");
+                window_shared().requests.quit = true;
+                LRESULT(0)
+            }
+            WM_GAME_FINISHED => {
                 DestroyWindow(window).expect("Unable to close window");
                 LRESULT(0)
             }
-            WM_SIZE => {
-                println!("WM_SIZE");
-
-                let dimensions = get_window_dimensions(window);
-                resize_dib_section(&mut BACK_BUFFER, dimensions.width, dimensions.height)
-                    .expect("Unable to resize dib section");
-
+            WM_UPDATE_RELATIVE_MOUSE => {
+                let mut shared = window_shared();
+                let shared = &mut *shared;
+                update_relative_mouse(window, &mut shared.cursor, &shared.input);
+                LRESULT(0)
+            }
+            WM_SYSKEYDOWN | WM_KEYDOWN | WM_SYSKEYUP | WM_KEYUP | WM_CHAR | WM_MOUSEMOVE | WM_MOUSEWHEEL |
+            WM_LBUTTONDOWN | WM_LBUTTONUP | WM_RBUTTONDOWN | WM_RBUTTONUP | WM_MBUTTONDOWN | WM_MBUTTONUP => {
+                handle_input_message(window, message, w_param, l_param);
                 LRESULT(0)
             }
             _ => DefWindowProcA(window, message, w_param, l_param)
@@ -174,112 +342,264 @@ extern "system" fn wnd_proc(window: HWND, message: u32, w_param: WPARAM, l_param
     }
 }
 
-unsafe fn process_pending_messages(input: &mut InputController) {
-    let mut message: MSG = MSG::default();
-
-    while PeekMessageA(&mut message, None, 0, 0, PM_REMOVE).into() {
-        match message.message {
-            WM_QUIT => {
-                IS_RUNNING = false;
-            },
-            WM_SYSKEYDOWN | WM_KEYDOWN | WM_SYSKEYUP | WM_KEYUP => {
-                let vk_code = message.wParam.0 as i32;
-                let was_down: bool = (message.lParam.0 & (1 << 30)) != 0;
-                let is_down: bool = (message.lParam.0 & (1 << 31)) == 0;
-
-                match vk_code as u8 as char {
-                    'W' => input.w.is_down = is_down,
-                    'A' => input.a.is_down = is_down,
-                    'S' => input.s.is_down = is_down,
-                    'D' => input.d.is_down = is_down,
-                    _ => match VIRTUAL_KEY(vk_code as u16) {
-                        VK_UP => input.up.is_down = is_down,
-                        VK_LEFT => input.left.is_down = is_down,
-                        VK_DOWN => input.down.is_down = is_down,
-                        VK_RIGHT => input.right.is_down = is_down,
-                        VK_ESCAPE => input.esc.is_down = is_down,
-                        _ => {}
-                    }
-                }
+// Adds the message to WindowShared::input for the game thread to pick up
+unsafe fn handle_input_message(window: HWND, message: u32, w_param: WPARAM, l_param: LPARAM) {
+    let time = services::now();
+    let mut guard = window_shared();
+    let shared = &mut *guard;
+    let input = &mut shared.input;
+
+    match message {
+        WM_SYSKEYDOWN | WM_KEYDOWN | WM_SYSKEYUP | WM_KEYUP => {
+            let vk_code = w_param.0 as i32;
+            let was_down: bool = (l_param.0 & (1 << 30)) != 0;
+            let is_down: bool = (l_param.0 & (1 << 31)) == 0;
+
+            let key = key_from_vk_code(vk_code, l_param.0);
+            // Auto repeat sends more WM_KEYDOWNs with was_down set, those aren't another KeyDown
+            if was_down != is_down {
+                input.events.push(time, if is_down { InputEvent::KeyDown(key) } else { InputEvent::KeyUp(key) });
+            }
 
-                if !was_down && is_down {
-                    // F5
-                    if VIRTUAL_KEY(vk_code as u16) == VK_F5 {
-                        println!("reload");
-                        crate::reload_lib();
-                    }
-
-                    // alt + F4
-                    if VIRTUAL_KEY(vk_code as u16) == VK_F4 && message.lParam.0 & (1 << 29) != 0 {
-                        IS_RUNNING = false;
-                    }
+            input.keyboard.set_is_down(key, is_down);
+
+            if !was_down && is_down {
+                // alt + F4
+                if key == Key::F4 && l_param.0 & (1 << 29) != 0 {
+                    shared.requests.quit = true;
+                } else {
+                    request_for_key(key, &mut shared.requests);
                 }
             }
-            WM_MOUSEMOVE => {
-                let mut mouse_point = POINT::default();
-                GetCursorPos(&mut mouse_point).expect("Unable to get cursor position");
-                ScreenToClient(message.hwnd, &mut mouse_point);
-                input.mouse_state.pos = Vector2u32 {
-                    x: mouse_point.x as u32,
-                    y: mouse_point.y as u32
-                };
-            }
-            WM_LBUTTONDOWN => input.mouse_state.left.is_down = true,
-            WM_LBUTTONUP => input.mouse_state.left.is_down = false,
-            WM_RBUTTONDOWN => input.mouse_state.right.is_down = true,
-            WM_RBUTTONUP => input.mouse_state.right.is_down = false,
-            WM_MBUTTONDOWN => input.mouse_state.middle.is_down = true,
-            WM_MBUTTONUP => input.mouse_state.middle.is_down = false,
-            WM_MOUSEWHEEL => {
-                let wheel_delta = (message.wParam.0 >> 16) as i16;
-                input.mouse_state.wheel_delta = wheel_delta;
+        }
+        // Client coordinates as two signed 16 bit values, negative left of and above the
+        // window while the mouse is captured
+        WM_MOUSEMOVE => {
+            let pos = Vector2i32 {
+                x: (l_param.0 & 0xffff) as i16 as i32,
+                y: ((l_param.0 >> 16) & 0xffff) as i16 as i32
+            };
+            shared.cursor.cursor_moved(pos, time, input);
+        }
+        WM_LBUTTONDOWN | WM_LBUTTONUP | WM_RBUTTONDOWN | WM_RBUTTONUP | WM_MBUTTONDOWN | WM_MBUTTONUP => {
+            let is_down = matches!(message, WM_LBUTTONDOWN | WM_RBUTTONDOWN | WM_MBUTTONDOWN);
+
+            let mouse_button = match message {
+                WM_LBUTTONDOWN | WM_LBUTTONUP => {
+                    input.mouse_state.left.is_down = is_down;
+                    MouseButton::Left
+                },
+                WM_RBUTTONDOWN | WM_RBUTTONUP => {
+                    input.mouse_state.right.is_down = is_down;
+                    MouseButton::Right
+                },
+                _ => {
+                    input.mouse_state.middle.is_down = is_down;
+                    MouseButton::Middle
+                }
+            };
+
+            input.events.push(time, if is_down { InputEvent::MouseButtonDown(mouse_button) } else { InputEvent::MouseButtonUp(mouse_button) });
+
+            // Keeps the mouse messages coming while a drag leaves the window, until the last button goes up.
+            // Capture changes send messages of their own, so not while holding the lock.
+            let mouse = &input.mouse_state;
+            let any_down = mouse.left.is_down || mouse.right.is_down || mouse.middle.is_down;
+            drop(guard);
+            if is_down {
+                SetCapture(window);
+            } else if !any_down {
+                let _ = ReleaseCapture();
             }
-            _ => {
-                TranslateMessage(&message);
-                DispatchMessageA(&message);
+        }
+        WM_MOUSEWHEEL => {
+            let wheel_delta = (w_param.0 >> 16) as i16;
+            input.mouse_state.wheel_delta = input.mouse_state.wheel_delta.saturating_add(wheel_delta);
+            input.events.push(time, InputEvent::Wheel(wheel_delta));
+        }
+        WM_CHAR => {
+            let code_unit = w_param.0 as u16;
+
+            let units = match shared.high_surrogate.take() {
+                Some(high) => vec![high, code_unit],
+                None if (0xD800..0xDC00).contains(&code_unit) => {
+                    shared.high_surrogate = Some(code_unit);
+                    return;
+                },
+                None => vec![code_unit]
+            };
+
+            for character in char::decode_utf16(units).flatten() {
+                if !character.is_control() {
+                    input.events.push(time, InputEvent::Text(character));
+                }
             }
         }
+        _ => {}
     }
 }
 
-unsafe fn resize_dib_section(buffer: &mut OffscreenBuffer, width: u32, height: u32) -> Result<()> {
-    if (&buffer).memory != null_mut() {
-        VirtualFree((&buffer).memory, 0, MEM_RELEASE)
-            .expect("Unable to free memory");
+// Switches relative mode on or off when the game asked for it, and while it's on
+// puts the cursor back in the middle of the window so it never stops at the screen edge
+unsafe fn update_relative_mouse(window: HWND, cursor: &mut CursorTracker, input: &InputController) {
+    let wants_relative = services::relative_mouse_requested();
+
+    if wants_relative && !cursor.is_relative {
+        ShowCursor(false);
+        cursor.is_relative = true;
+    } else if !wants_relative && cursor.is_relative {
+        let _ = ClipCursor(None);
+        ShowCursor(true);
+
+        let pos = input.mouse_state.pos;
+        let mut point = POINT { x: pos.x, y: pos.y };
+        ClientToScreen(window, &mut point);
+        let _ = SetCursorPos(point.x, point.y);
+        cursor.last_pos = Some(pos);
+        cursor.is_relative = false;
+    }
+
+    if cursor.is_relative {
+        // Clipped again every frame since the window can move or resize in the meantime
+        let mut client_rect = RECT::default();
+        let _ = GetClientRect(window, &mut client_rect);
+        let mut top_left = POINT { x: client_rect.left, y: client_rect.top };
+        let mut bottom_right = POINT { x: client_rect.right, y: client_rect.bottom };
+        ClientToScreen(window, &mut top_left);
+        ClientToScreen(window, &mut bottom_right);
+        let clip_rect = RECT { left: top_left.x, top: top_left.y, right: bottom_right.x, bottom: bottom_right.y };
+        let _ = ClipCursor(Some(&clip_rect));
+
+        let center = Vector2i32 { x: client_rect.right / 2, y: client_rect.bottom / 2 };
+        if cursor.last_pos != Some(center) {
+            // Comes back as a WM_MOUSEMOVE to the center, which then moves by nothing
+            let mut point = POINT { x: center.x, y: center.y };
+            ClientToScreen(window, &mut point);
+            let _ = SetCursorPos(point.x, point.y);
+            cursor.last_pos = Some(center);
+        }
     }
+}
+
+// l_param tells the left and right modifier keys apart, the virtual key code doesn't
+fn key_from_vk_code(vk_code: i32, l_param: isize) -> Key {
+    let scan_code = (l_param >> 16) & 0xff;
+    let is_extended = l_param & (1 << 24) != 0;
+
+    let code = vk_code as u16;
+    match VIRTUAL_KEY(code) {
+        _ if (b'A' as u16..=b'Z' as u16).contains(&code) => Key::letter((code - b'A' as u16) as u32),
+        _ if (b'0' as u16..=b'9' as u16).contains(&code) => Key::digit((code - b'0' as u16) as u32),
+        _ if (VK_F1.0..=VK_F12.0).contains(&code) => Key::function((code - VK_F1.0 + 1) as u32),
+        VK_UP => Key::Up,
+        VK_LEFT => Key::Left,
+        VK_DOWN => Key::Down,
+        VK_RIGHT => Key::Right,
+        VK_ESCAPE => Key::Escape,
+        VK_TAB => Key::Tab,
+        VK_SPACE => Key::Space,
+        VK_RETURN => Key::Enter,
+        VK_BACK => Key::Backspace,
+        VK_INSERT => Key::Insert,
+        VK_DELETE => Key::Delete,
+        VK_HOME => Key::Home,
+        VK_END => Key::End,
+        VK_PRIOR => Key::PageUp,
+        VK_NEXT => Key::PageDown,
+        // Right shift has its own scan code, the others are extended keys
+        VK_SHIFT if scan_code == 0x36 => Key::RightShift,
+        VK_SHIFT => Key::LeftShift,
+        VK_CONTROL if is_extended => Key::RightControl,
+        VK_CONTROL => Key::LeftControl,
+        VK_MENU if is_extended => Key::RightAlt,
+        VK_MENU => Key::LeftAlt,
+        VK_OEM_MINUS => Key::Minus,
+        VK_OEM_PLUS => Key::Equals,
+        VK_OEM_4 => Key::LeftBracket,
+        VK_OEM_6 => Key::RightBracket,
+        VK_OEM_5 => Key::Backslash,
+        VK_OEM_1 => Key::Semicolon,
+        VK_OEM_7 => Key::Apostrophe,
+        VK_OEM_COMMA => Key::Comma,
+        VK_OEM_PERIOD => Key::Period,
+        VK_OEM_2 => Key::Slash,
+        VK_OEM_3 => Key::Grave,
+        _ => Key::Unknown
+    }
+}
+
+unsafe fn poll_gamepads(gamepads: &mut [GamepadState; MAX_GAMEPADS], scan_disconnected: bool) {
+    let mut i = 0;
+    while i < MAX_GAMEPADS {
+        if gamepads[i].is_connected || scan_disconnected {
+            let mut state = XINPUT_STATE::default();
+
+            if XInputGetState(i as u32, &mut state) == ERROR_SUCCESS.0 {
+                let pad = state.Gamepad;
+                let gamepad = &mut gamepads[i];
+
+                gamepad.is_connected = true;
+                gamepad.left_stick = apply_stick_deadzone(pad.sThumbLX as f32 / 32767.0, pad.sThumbLY as f32 / 32767.0, LEFT_STICK_DEADZONE);
+                gamepad.right_stick = apply_stick_deadzone(pad.sThumbRX as f32 / 32767.0, pad.sThumbRY as f32 / 32767.0, RIGHT_STICK_DEADZONE);
+                gamepad.left_trigger = apply_trigger_threshold(pad.bLeftTrigger as f32 / 255.0);
+                gamepad.right_trigger = apply_trigger_threshold(pad.bRightTrigger as f32 / 255.0);
+
+                for (button, flag) in XINPUT_BUTTON_MAP {
+                    gamepad.set_is_down(button, pad.wButtons.0 & flag.0 != 0);
+                }
+            } else {
+                gamepads[i] = GamepadState::default();
+            }
+        }
+
+        i += 1;
+    }
+}
 
-    (*buffer).width = width;
-    (*buffer).height = height;
-    (*buffer).bytes_per_pixel = 4;
+unsafe fn resize_dib_section(buffer: &mut Win32OffscreenBuffer, width: u32, height: u32) -> Result<()> {
+    free_dib_section(buffer);
+
+    (*buffer).buffer.width = width;
+    (*buffer).buffer.height = height;
+    (*buffer).buffer.bytes_per_pixel = 4;
+    (*buffer).buffer.pixel_format = PixelFormat::Bgra8;
 
     (*buffer).info = BITMAPINFO::default();
 
     (*buffer).info.bmiHeader.biSize = size_of::<BITMAPINFOHEADER>() as u32;
-    (*buffer).info.bmiHeader.biWidth = (&buffer).width as i32;
-    (*buffer).info.bmiHeader.biHeight = -((&buffer).height as i32);
+    (*buffer).info.bmiHeader.biWidth = (&buffer).buffer.width as i32;
+    (*buffer).info.bmiHeader.biHeight = -((&buffer).buffer.height as i32);
     (*buffer).info.bmiHeader.biPlanes = 1;
     (*buffer).info.bmiHeader.biBitCount = 32;
     (*buffer).info.bmiHeader.biCompression = 0;
 
-    let bitmap_memory_size = (width * height) * (*buffer).bytes_per_pixel;
-    (*buffer).memory = VirtualAlloc(
+    let bitmap_memory_size = (width * height) * (*buffer).buffer.bytes_per_pixel;
+    (*buffer).buffer.memory = VirtualAlloc(
         None,
         bitmap_memory_size.try_into().unwrap(),
         MEM_COMMIT,
         PAGE_READWRITE);
 
-    (*buffer).pitch = (*buffer).width * (*buffer).bytes_per_pixel;
+    (*buffer).buffer.pitch = (*buffer).buffer.width * (*buffer).buffer.bytes_per_pixel;
 
     Ok(())
 }
 
-unsafe fn copy_buffer_to_window(buffer: &mut OffscreenBuffer, device_context: HDC, width: u32, height: u32)
+unsafe fn free_dib_section(buffer: &mut Win32OffscreenBuffer) {
+    if !buffer.buffer.memory.is_null() {
+        VirtualFree(buffer.buffer.memory, 0, MEM_RELEASE)
+            .expect("Unable to free memory");
+        buffer.buffer.memory = null_mut();
+    }
+}
+
+unsafe fn copy_buffer_to_window(buffer: &Win32OffscreenBuffer, device_context: HDC, width: u32, height: u32)
     -> Result<()> {
     StretchDIBits(
         device_context,
         0, 0, width as i32, height as i32,
-        0, 0, (*buffer).width as i32, (*buffer).height as i32,
-        Some((*buffer).memory as *const c_void),
+        0, 0, (*buffer).buffer.width as i32, (*buffer).buffer.height as i32,
+        Some((*buffer).buffer.memory as *const c_void),
         &(*buffer).info,
         DIB_RGB_COLORS, SRCCOPY);
 
@@ -302,30 +622,3 @@ unsafe fn get_window_dimensions(window: HWND) -> WindowDimensions {
         }
     }
 }
-
-unsafe fn game_update_and_render(game_state: &mut GameState, input_controller: &mut InputController, buffer: &mut OffscreenBuffer) {
-    match &GAME_UPDATE_AND_RENDER {
-        Some(func) => {
-            func(game_state, input_controller, buffer);
-        },
-        None => {
-            let lib = match &LIBRARY {
-                Some(value) => value,
-                None => {
-                    eprintln!("Library not initialized");
-                    return
-                }
-            };
-
-            let func: libloading::Symbol<unsafe extern fn(game_state: &mut GameState, input_controller: &mut InputController, buffer: &mut OffscreenBuffer) -> ()> =
-                match lib.get(b"game_update_and_render") {
-                    Ok(value) => value,
-                    Err(error) => panic!("Unable to get game_update_and_render from oxide: {}", error)
-                };
-
-            GAME_UPDATE_AND_RENDER = Some(func.clone());
-
-            func(game_state, input_controller, buffer);
-        }
-    };
-}
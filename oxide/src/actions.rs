@@ -0,0 +1,605 @@
+use crate::*;
+
+// Looked for in the host's working directory, see default_bindings.cfg for the format
+pub const BINDINGS_PATH: &str = "bindings.cfg";
+const DEFAULT_BINDINGS: &str = include_str!("../default_bindings.cfg");
+
+// How often bindings.cfg is read again to see if it changed, in milliseconds
+const BINDINGS_CHECK_INTERVAL: u128 = 1000;
+
+pub const MAX_BINDINGS: usize = 64;
+
+// Everything the editor responds to. Game logic asks for these instead of physical inputs.
+// The discriminant indexes ActionStates, so Count stays last.
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    PanUp,
+    PanDown,
+    PanLeft,
+    PanRight,
+    ZoomIn,
+    ZoomOut,
+    ResetCamera,
+    SelectPoint,
+    // Pans with the mouse while held, the cursor is hidden and stays put meanwhile
+    LockedPan,
+    MovePointUp,
+    MovePointDown,
+    MovePointLeft,
+    MovePointRight,
+    NextPoint,
+    PreviousPoint,
+    Undo,
+    // Gives the selected curve the next fill color, or none
+    CycleFill,
+    Count
+}
+
+pub const ACTION_COUNT: usize = Action::Count as usize;
+
+const ACTION_NAMES: [(&str, Action); ACTION_COUNT] = [
+    ("PanUp", Action::PanUp),
+    ("PanDown", Action::PanDown),
+    ("PanLeft", Action::PanLeft),
+    ("PanRight", Action::PanRight),
+    ("ZoomIn", Action::ZoomIn),
+    ("ZoomOut", Action::ZoomOut),
+    ("ResetCamera", Action::ResetCamera),
+    ("SelectPoint", Action::SelectPoint),
+    ("LockedPan", Action::LockedPan),
+    ("MovePointUp", Action::MovePointUp),
+    ("MovePointDown", Action::MovePointDown),
+    ("MovePointLeft", Action::MovePointLeft),
+    ("MovePointRight", Action::MovePointRight),
+    ("NextPoint", Action::NextPoint),
+    ("PreviousPoint", Action::PreviousPoint),
+    ("Undo", Action::Undo),
+    ("CycleFill", Action::CycleFill)
+];
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GamepadAxis {
+    LeftStickUp,
+    LeftStickDown,
+    LeftStickLeft,
+    LeftStickRight,
+    RightStickUp,
+    RightStickDown,
+    RightStickLeft,
+    RightStickRight,
+    LeftTrigger,
+    RightTrigger
+}
+
+// Key has to stay the first variant so a zeroed Binding is still a valid one
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PhysicalInput {
+    Key(Key),
+    MouseButton(MouseButton),
+    // Up is away from the user
+    Wheel { up: bool },
+    // On any connected gamepad
+    GamepadButton(GamepadButton),
+    GamepadAxis(GamepadAxis)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Binding {
+    pub action: Action,
+    pub input: PhysicalInput,
+    // Have to be held along with input
+    pub modifiers: Modifiers
+}
+
+#[derive(Clone, Copy)]
+pub struct Bindings {
+    count: u32,
+    bindings: [Binding; MAX_BINDINGS]
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Bindings {
+            count: 0,
+            bindings: [Binding { action: Action::PanUp, input: PhysicalInput::Key(Key::Unknown), modifiers: Modifiers::default() }; MAX_BINDINGS]
+        }
+    }
+}
+
+impl Bindings {
+    pub fn as_slice(&self) -> &[Binding] {
+        &self.bindings[..self.count as usize]
+    }
+
+    // Reads the bindings file format, complaining through services about anything it doesn't understand
+    pub fn parse(services: &PlatformServices, source_name: &str, text: &str) -> Bindings {
+        let mut bindings = Bindings::default();
+
+        for (line_index, line) in text.lines().enumerate() {
+            let line = match line.find('#') {
+                Some(index) => &line[..index],
+                None => line
+            }.trim();
+
+            if line.is_empty() {
+                continue;
+            }
+
+            let (action_name, inputs) = match line.split_once('=') {
+                Some(parts) => parts,
+                None => {
+                    log!(services, LogLevel::Warning, "{}:{}: expected Action = Input, ...", source_name, line_index + 1);
+                    continue;
+                }
+            };
+
+            let action = match ACTION_NAMES.iter().find(|(name, _)| *name == action_name.trim()) {
+                Some((_, action)) => *action,
+                None => {
+                    log!(services, LogLevel::Warning, "{}:{}: unknown action {}", source_name, line_index + 1, action_name.trim());
+                    continue;
+                }
+            };
+
+            for input in inputs.split(',').map(|input| input.trim()).filter(|input| !input.is_empty()) {
+                let binding = match parse_binding(action, input) {
+                    Some(binding) => binding,
+                    None => {
+                        log!(services, LogLevel::Warning, "{}:{}: unknown input {}", source_name, line_index + 1, input);
+                        continue;
+                    }
+                };
+
+                if bindings.count as usize == MAX_BINDINGS {
+                    log!(services, LogLevel::Warning, "{}:{}: more than {} bindings, ignoring {}", source_name, line_index + 1, MAX_BINDINGS, input);
+                    continue;
+                }
+
+                bindings.bindings[bindings.count as usize] = binding;
+                bindings.count += 1;
+            }
+        }
+
+        bindings
+    }
+}
+
+// "Ctrl+Shift+Z", "GamepadA", "WheelUp"
+fn parse_binding(action: Action, text: &str) -> Option<Binding> {
+    let mut modifiers = Modifiers::default();
+    let mut parts = text.split('+').map(|part| part.trim()).peekable();
+
+    let mut input_name = "";
+    while let Some(part) = parts.next() {
+        if parts.peek().is_none() {
+            input_name = part;
+            break;
+        }
+
+        match part {
+            "Ctrl" | "Control" => modifiers.control = true,
+            "Shift" => modifiers.shift = true,
+            "Alt" => modifiers.alt = true,
+            _ => return None
+        }
+    }
+
+    Some(Binding {
+        action,
+        input: parse_physical_input(input_name)?,
+        modifiers
+    })
+}
+
+fn parse_physical_input(name: &str) -> Option<PhysicalInput> {
+    let input = match name {
+        "MouseLeft" => PhysicalInput::MouseButton(MouseButton::Left),
+        "MouseRight" => PhysicalInput::MouseButton(MouseButton::Right),
+        "MouseMiddle" => PhysicalInput::MouseButton(MouseButton::Middle),
+        "WheelUp" => PhysicalInput::Wheel { up: true },
+        "WheelDown" => PhysicalInput::Wheel { up: false },
+        "GamepadA" => PhysicalInput::GamepadButton(GamepadButton::A),
+        "GamepadB" => PhysicalInput::GamepadButton(GamepadButton::B),
+        "GamepadX" => PhysicalInput::GamepadButton(GamepadButton::X),
+        "GamepadY" => PhysicalInput::GamepadButton(GamepadButton::Y),
+        "GamepadLeftShoulder" => PhysicalInput::GamepadButton(GamepadButton::LeftShoulder),
+        "GamepadRightShoulder" => PhysicalInput::GamepadButton(GamepadButton::RightShoulder),
+        "GamepadBack" => PhysicalInput::GamepadButton(GamepadButton::Back),
+        "GamepadStart" => PhysicalInput::GamepadButton(GamepadButton::Start),
+        "GamepadLeftThumb" => PhysicalInput::GamepadButton(GamepadButton::LeftThumb),
+        "GamepadRightThumb" => PhysicalInput::GamepadButton(GamepadButton::RightThumb),
+        "GamepadDpadUp" => PhysicalInput::GamepadButton(GamepadButton::DpadUp),
+        "GamepadDpadDown" => PhysicalInput::GamepadButton(GamepadButton::DpadDown),
+        "GamepadDpadLeft" => PhysicalInput::GamepadButton(GamepadButton::DpadLeft),
+        "GamepadDpadRight" => PhysicalInput::GamepadButton(GamepadButton::DpadRight),
+        "GamepadLeftStickUp" => PhysicalInput::GamepadAxis(GamepadAxis::LeftStickUp),
+        "GamepadLeftStickDown" => PhysicalInput::GamepadAxis(GamepadAxis::LeftStickDown),
+        "GamepadLeftStickLeft" => PhysicalInput::GamepadAxis(GamepadAxis::LeftStickLeft),
+        "GamepadLeftStickRight" => PhysicalInput::GamepadAxis(GamepadAxis::LeftStickRight),
+        "GamepadRightStickUp" => PhysicalInput::GamepadAxis(GamepadAxis::RightStickUp),
+        "GamepadRightStickDown" => PhysicalInput::GamepadAxis(GamepadAxis::RightStickDown),
+        "GamepadRightStickLeft" => PhysicalInput::GamepadAxis(GamepadAxis::RightStickLeft),
+        "GamepadRightStickRight" => PhysicalInput::GamepadAxis(GamepadAxis::RightStickRight),
+        "GamepadLeftTrigger" => PhysicalInput::GamepadAxis(GamepadAxis::LeftTrigger),
+        "GamepadRightTrigger" => PhysicalInput::GamepadAxis(GamepadAxis::RightTrigger),
+        _ => PhysicalInput::Key(Key::from_name(name)?)
+    };
+
+    Some(input)
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct ActionState {
+    // Down while any of its bindings is
+    pub button: ButtonState,
+    // 0 to 1, the strongest of the held bindings. Analog for sticks and triggers, 1 for the rest.
+    pub value: f32,
+    // Wheel notches this frame, for actions that should happen once per notch rather than while held
+    pub steps: f32,
+    // A binding went down and back up within the frame, so button never saw it
+    pressed_within_frame: bool
+}
+
+impl ActionState {
+    pub fn pressed(&self) -> bool {
+        self.button.pressed() || self.pressed_within_frame
+    }
+
+    pub fn released(&self) -> bool {
+        self.button.released()
+    }
+
+    pub fn held(&self) -> bool {
+        self.button.held()
+    }
+}
+
+#[derive(Clone, Copy, Default)]
+pub struct ActionStates {
+    states: [ActionState; ACTION_COUNT]
+}
+
+impl ActionStates {
+    pub fn get(&self, action: Action) -> ActionState {
+        self.states[action as usize]
+    }
+
+    // Works the actions out from this frame's input, was_down comes from the last call
+    pub fn update(&mut self, bindings: &Bindings, input: &InputController) {
+        let mut new_states = [ActionState::default(); ACTION_COUNT];
+
+        for binding in bindings.as_slice() {
+            let state = &mut new_states[binding.action as usize];
+
+            if !modifiers_match(binding, input.modifiers) {
+                continue;
+            }
+
+            match binding.input {
+                PhysicalInput::Key(key) => {
+                    if input.held(key) {
+                        state.button.is_down = true;
+                        state.value = 1.0;
+                    }
+                    if input.events.iter().any(|event| event.event == InputEvent::KeyDown(key)) {
+                        state.pressed_within_frame = true;
+                    }
+                },
+                PhysicalInput::MouseButton(mouse_button) => {
+                    let button = match mouse_button {
+                        MouseButton::Left => input.mouse_state.left,
+                        MouseButton::Right => input.mouse_state.right,
+                        MouseButton::Middle => input.mouse_state.middle
+                    };
+                    if button.held() {
+                        state.button.is_down = true;
+                        state.value = 1.0;
+                    }
+                    if input.events.iter().any(|event| event.event == InputEvent::MouseButtonDown(mouse_button)) {
+                        state.pressed_within_frame = true;
+                    }
+                },
+                PhysicalInput::Wheel { up } => {
+                    let notches = input.mouse_state.wheel_delta as f32 / 120.0;
+                    let notches = if up { notches } else { -notches };
+                    if notches > 0.0 {
+                        state.button.is_down = true;
+                        state.steps += notches;
+                    }
+                },
+                PhysicalInput::GamepadButton(gamepad_button) => {
+                    for gamepad in input.gamepads.iter().filter(|gamepad| gamepad.is_connected) {
+                        if gamepad.button(gamepad_button).held() {
+                            state.button.is_down = true;
+                            state.value = 1.0;
+                        }
+                    }
+                },
+                PhysicalInput::GamepadAxis(axis) => {
+                    for gamepad in input.gamepads.iter().filter(|gamepad| gamepad.is_connected) {
+                        let value = axis_value(gamepad, axis);
+                        if value > 0.0 {
+                            state.button.is_down = true;
+                            state.value = state.value.max(value);
+                        }
+                    }
+                }
+            }
+        }
+
+        let mut i = 0;
+        while i < ACTION_COUNT {
+            let new_state = new_states[i];
+            let state = &mut self.states[i];
+
+            state.button.update(new_state.button.is_down);
+            state.value = new_state.value;
+            state.steps = new_state.steps;
+            state.pressed_within_frame = new_state.pressed_within_frame;
+
+            i += 1;
+        }
+    }
+}
+
+// Keys need exactly the written modifiers, apart from the one the key itself turns on.
+// Everything else only needs the written ones held.
+fn modifiers_match(binding: &Binding, held: Modifiers) -> bool {
+    let required = binding.modifiers;
+
+    match binding.input {
+        PhysicalInput::Key(key) => {
+            let mut held = held;
+            match key {
+                Key::LeftShift | Key::RightShift => held.shift = required.shift,
+                Key::LeftControl | Key::RightControl => held.control = required.control,
+                Key::LeftAlt | Key::RightAlt => held.alt = required.alt,
+                _ => {}
+            }
+            held == required
+        },
+        _ => (!required.shift || held.shift) && (!required.control || held.control) && (!required.alt || held.alt)
+    }
+}
+
+fn axis_value(gamepad: &GamepadState, axis: GamepadAxis) -> f32 {
+    match axis {
+        GamepadAxis::LeftStickUp => gamepad.left_stick.y.max(0.0),
+        GamepadAxis::LeftStickDown => (-gamepad.left_stick.y).max(0.0),
+        GamepadAxis::LeftStickLeft => (-gamepad.left_stick.x).max(0.0),
+        GamepadAxis::LeftStickRight => gamepad.left_stick.x.max(0.0),
+        GamepadAxis::RightStickUp => gamepad.right_stick.y.max(0.0),
+        GamepadAxis::RightStickDown => (-gamepad.right_stick.y).max(0.0),
+        GamepadAxis::RightStickLeft => (-gamepad.right_stick.x).max(0.0),
+        GamepadAxis::RightStickRight => gamepad.right_stick.x.max(0.0),
+        GamepadAxis::LeftTrigger => gamepad.left_trigger,
+        GamepadAxis::RightTrigger => gamepad.right_trigger
+    }
+}
+
+// The bindings in use and what's needed to notice bindings.cfg changing
+#[derive(Clone, Copy, Default)]
+pub struct InputMapping {
+    pub bindings: Bindings,
+    pub actions: ActionStates,
+    is_loaded: bool,
+    // Of what bindings.cfg held last time, 0 while there isn't one
+    file_hash: u64,
+    last_check: u128
+}
+
+impl InputMapping {
+    // Has the next reload_if_changed parse the bindings again even if bindings.cfg is the same,
+    // a new build of the library may have different default bindings or parse them differently
+    pub fn force_reload(&mut self) {
+        self.is_loaded = false;
+    }
+
+    // Loads the bindings on the first call and again whenever bindings.cfg changes.
+    // Changes are found by reading the file once in a while, it's small and there's no file watching in services.
+    pub fn reload_if_changed(&mut self, services: &PlatformServices, arena: &mut MemoryArena) {
+        let time_now = services.now() as u128;
+        if self.is_loaded && time_now.abs_diff(self.last_check) < BINDINGS_CHECK_INTERVAL {
+            return;
+        }
+        self.last_check = time_now;
+
+        let mark = arena.mark();
+
+        let contents = services.read_entire_file(arena, BINDINGS_PATH);
+        let file_hash = match &contents {
+            // 0 is kept for "no file"
+            Some(contents) => hash_bytes(FNV_OFFSET_BASIS, contents).max(1),
+            None => 0
+        };
+
+        if !self.is_loaded || file_hash != self.file_hash {
+            match contents.as_deref().map(|contents| std::str::from_utf8(contents)) {
+                Some(Ok(text)) => {
+                    self.bindings = Bindings::parse(services, BINDINGS_PATH, text);
+                    log!(services, LogLevel::Info, "Loaded {} bindings from {}", self.bindings.count, BINDINGS_PATH);
+                },
+                Some(Err(_)) => {
+                    log!(services, LogLevel::Warning, "{} isn't valid UTF-8, keeping the bindings as they were", BINDINGS_PATH);
+                    if !self.is_loaded {
+                        self.bindings = Bindings::parse(services, "default bindings", DEFAULT_BINDINGS);
+                    }
+                },
+                None => {
+                    self.bindings = Bindings::parse(services, "default bindings", DEFAULT_BINDINGS);
+                }
+            }
+
+            self.is_loaded = true;
+            self.file_hash = file_hash;
+        }
+
+        arena.pop_to_mark(mark);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str) -> Bindings {
+        Bindings::parse(&stub_services(), "test bindings", text)
+    }
+
+    // A frame where exactly keys are down, with events happening in between
+    fn frame(input: &mut InputController, keys: &[Key], events: &[InputEvent]) {
+        let mut new_input = InputController::default();
+        for key in keys {
+            new_input.keyboard.set_is_down(*key, true);
+        }
+        for event in events {
+            new_input.events.push(0.0, *event);
+        }
+        input.update(new_input);
+    }
+
+    #[test]
+    fn parses_actions_inputs_and_modifiers() {
+        let bindings = parse("Undo = Ctrl+Shift+Z # redo some day\n\n  PanUp = W, Up,GamepadDpadUp\nZoomIn = WheelUp");
+        let bindings = bindings.as_slice();
+
+        assert_eq!(bindings.len(), 5);
+        assert_eq!(bindings[0], Binding {
+            action: Action::Undo,
+            input: PhysicalInput::Key(Key::Z),
+            modifiers: Modifiers { shift: true, control: true, alt: false }
+        });
+        assert_eq!(bindings[1].input, PhysicalInput::Key(Key::W));
+        assert_eq!(bindings[2].input, PhysicalInput::Key(Key::Up));
+        assert_eq!(bindings[3].input, PhysicalInput::GamepadButton(GamepadButton::DpadUp));
+        assert_eq!(bindings[4], Binding { action: Action::ZoomIn, input: PhysicalInput::Wheel { up: true }, modifiers: Modifiers::default() });
+    }
+
+    #[test]
+    fn skips_unknown_names_and_malformed_lines() {
+        let bindings = parse("PanUp W\nPanSideways = A\n= S\nPanDown = Bogus, S, Meta+D, Ctrl+, w\nPanLeft =");
+        let bindings = bindings.as_slice();
+
+        assert_eq!(bindings.len(), 1);
+        assert_eq!(bindings[0].action, Action::PanDown);
+        assert_eq!(bindings[0].input, PhysicalInput::Key(Key::S));
+    }
+
+    #[test]
+    fn stops_at_max_bindings() {
+        let mut text = String::new();
+        for _ in 0..MAX_BINDINGS + 3 {
+            text.push_str("PanUp = W\n");
+        }
+        assert_eq!(parse(&text).as_slice().len(), MAX_BINDINGS);
+    }
+
+    #[test]
+    fn key_names() {
+        assert_eq!(Key::from_name("A"), Some(Key::A));
+        assert_eq!(Key::from_name("7"), Some(Key::Num7));
+        assert_eq!(Key::from_name("F"), Some(Key::F));
+        assert_eq!(Key::from_name("F12"), Some(Key::F12));
+        assert_eq!(Key::from_name("PageUp"), Some(Key::PageUp));
+        assert_eq!(Key::from_name("F13"), None);
+        assert_eq!(Key::from_name("F0"), None);
+        assert_eq!(Key::from_name("a"), None);
+        assert_eq!(Key::from_name("pageup"), None);
+        assert_eq!(Key::from_name("Unknown"), None);
+        assert_eq!(Key::from_name(""), None);
+    }
+
+    #[test]
+    fn ctrl_z_and_plain_z_dont_both_fire() {
+        let bindings = parse("Undo = Ctrl+Z\nCycleFill = Z");
+        let mut input = InputController::default();
+        let mut actions = ActionStates::default();
+
+        frame(&mut input, &[Key::LeftControl, Key::Z], &[]);
+        actions.update(&bindings, &input);
+        assert!(actions.get(Action::Undo).pressed());
+        assert!(!actions.get(Action::CycleFill).held());
+
+        frame(&mut input, &[Key::Z], &[]);
+        actions.update(&bindings, &input);
+        assert!(!actions.get(Action::Undo).held());
+        assert!(actions.get(Action::CycleFill).held());
+    }
+
+    #[test]
+    fn modifier_keys_can_be_bound_on_their_own() {
+        let bindings = parse("LockedPan = LeftShift\nMovePointUp = Alt+MouseLeft");
+        let mut input = InputController::default();
+        let mut actions = ActionStates::default();
+
+        frame(&mut input, &[Key::LeftShift], &[]);
+        actions.update(&bindings, &input);
+        assert!(actions.get(Action::LockedPan).held());
+
+        // Other inputs only need the written modifiers, holding more is fine
+        let mut new_input = InputController::default();
+        new_input.keyboard.set_is_down(Key::LeftAlt, true);
+        new_input.keyboard.set_is_down(Key::LeftShift, true);
+        new_input.mouse_state.left.is_down = true;
+        input.update(new_input);
+        actions.update(&bindings, &input);
+        assert!(actions.get(Action::MovePointUp).held());
+    }
+
+    #[test]
+    fn press_and_release_within_one_frame_is_a_press() {
+        let bindings = parse("Undo = Ctrl+Z\nSelectPoint = MouseLeft");
+        let mut input = InputController::default();
+        let mut actions = ActionStates::default();
+
+        frame(&mut input, &[Key::LeftControl], &[
+            InputEvent::KeyDown(Key::Z),
+            InputEvent::KeyUp(Key::Z),
+            InputEvent::MouseButtonDown(MouseButton::Left),
+            InputEvent::MouseButtonUp(MouseButton::Left)
+        ]);
+        actions.update(&bindings, &input);
+        assert!(actions.get(Action::Undo).pressed());
+        assert!(!actions.get(Action::Undo).held());
+        assert!(actions.get(Action::SelectPoint).pressed());
+        assert!(!actions.get(Action::SelectPoint).held());
+
+        frame(&mut input, &[Key::LeftControl], &[]);
+        actions.update(&bindings, &input);
+        assert!(!actions.get(Action::Undo).pressed());
+        assert!(!actions.get(Action::SelectPoint).pressed());
+    }
+
+    #[test]
+    fn press_within_frame_needs_the_modifiers() {
+        let bindings = parse("Undo = Ctrl+Z");
+        let mut input = InputController::default();
+        let mut actions = ActionStates::default();
+
+        frame(&mut input, &[], &[InputEvent::KeyDown(Key::Z), InputEvent::KeyUp(Key::Z)]);
+        actions.update(&bindings, &input);
+        assert!(!actions.get(Action::Undo).pressed());
+    }
+
+    #[test]
+    fn force_reload_parses_the_bindings_again() {
+        let services = stub_services();
+        let mut storage = vec![0u64; 1024];
+        let mut arena = MemoryArena::new(storage.as_mut_ptr() as *mut std::ffi::c_void, 8 * 1024);
+        let mut mapping = InputMapping::default();
+
+        mapping.reload_if_changed(&services, &mut arena);
+        let default_count = mapping.bindings.as_slice().len();
+        assert!(default_count > 0);
+
+        // Stale bindings from an older build of the library
+        mapping.bindings = Bindings::default();
+        mapping.reload_if_changed(&services, &mut arena);
+        assert!(mapping.bindings.as_slice().is_empty());
+
+        mapping.force_reload();
+        mapping.reload_if_changed(&services, &mut arena);
+        assert_eq!(mapping.bindings.as_slice().len(), default_count);
+    }
+}
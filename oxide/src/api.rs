@@ -0,0 +1,57 @@
+use crate::*;
+
+// Bump whenever GameApi or the signature of one of its functions changes
+pub const GAME_API_VERSION: u32 = 2;
+
+// Every entry point the host calls, handed over in one go by game_get_api.
+// All of them use the C ABI so host and library agree on how to call each other
+// no matter which compiler built which.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct GameApi {
+    pub version: u32,
+    // Once, with fresh zeroed memory, before the first update
+    pub init: unsafe extern "C" fn(memory: &mut GameMemory),
+    // A fixed step of the simulation, as many times per frame as the host's clock calls for
+    pub update: unsafe extern "C" fn(memory: &mut GameMemory, input_controller: &mut InputController, screen: &WindowDimensions),
+    // Once per frame, blend_factor from 0 to 1 is how far the frame is from the last update to the next
+    pub render: unsafe extern "C" fn(memory: &mut GameMemory, buffer: &mut OffscreenBuffer, blend_factor: f32),
+    pub get_sound_samples: unsafe extern "C" fn(memory: &mut GameMemory, sound_buffer: &mut SoundOutputBuffer),
+    // After the host swapped in a new build of the library, memory is the same as before
+    pub on_reload: unsafe extern "C" fn(memory: &mut GameMemory),
+    // Before the host exits or frees the memory
+    pub shutdown: unsafe extern "C" fn(memory: &mut GameMemory)
+}
+
+#[no_mangle]
+pub extern "C" fn game_get_api() -> GameApi {
+    GameApi {
+        version: GAME_API_VERSION,
+        init: game_init,
+        update: game_update,
+        render: game_render,
+        get_sound_samples: game_get_sound_samples,
+        on_reload: game_on_reload,
+        shutdown: game_shutdown
+    }
+}
+
+unsafe extern "C" fn game_init(memory: &mut GameMemory) {
+    get_game_state(memory);
+}
+
+// Nothing makes sound yet
+unsafe extern "C" fn game_get_sound_samples(_memory: &mut GameMemory, sound_buffer: &mut SoundOutputBuffer) {
+    let sample_count = (sound_buffer.sample_count * sound_buffer.channel_count) as usize;
+    if !sound_buffer.samples.is_null() {
+        std::ptr::write_bytes(sound_buffer.samples, 0, sample_count);
+    }
+}
+
+// Runs the GameState migration right away instead of waiting for the next frame, and has the
+// bindings parsed again since the new build may not agree with how the old one parsed them
+unsafe extern "C" fn game_on_reload(memory: &mut GameMemory) {
+    get_game_state(memory).input_mapping.force_reload();
+}
+
+unsafe extern "C" fn game_shutdown(_memory: &mut GameMemory) {}
@@ -0,0 +1,120 @@
+use std::ffi::c_void;
+use std::mem::{align_of, size_of};
+use std::ptr;
+use std::ptr::null_mut;
+use std::slice;
+
+// Bump allocator over a block of GameMemory. Nothing is freed on its own,
+// the whole arena gets reset (every frame for the transient one) or rolled back to a mark.
+// All zeroes is a valid, empty arena, so it can sit in zero initialized memory.
+pub struct MemoryArena {
+    base: *mut u8,
+    size: usize,
+    used: usize,
+    high_water_mark: usize
+}
+
+impl Default for MemoryArena {
+    fn default() -> Self {
+        MemoryArena {
+            base: null_mut(),
+            size: 0,
+            used: 0,
+            high_water_mark: 0
+        }
+    }
+}
+
+impl MemoryArena {
+    pub fn new(base: *mut c_void, size: u64) -> MemoryArena {
+        MemoryArena {
+            base: base as *mut u8,
+            size: size as usize,
+            used: 0,
+            high_water_mark: 0
+        }
+    }
+
+    // For when the block the arena lives in was moved, keeps everything that's been pushed.
+    // Anything holding pointers into the old block is on its own.
+    pub fn rebase(&mut self, base: *mut c_void, size: u64) {
+        assert!(self.used as u64 <= size, "Arena doesn't fit in its new block");
+        self.base = base as *mut u8;
+        self.size = size as usize;
+    }
+
+    pub fn push_struct<T>(&mut self, value: T) -> &mut T {
+        unsafe {
+            let pointer = self.push_bytes(size_of::<T>(), align_of::<T>()) as *mut T;
+            ptr::write(pointer, value);
+            &mut *pointer
+        }
+    }
+
+    // count copies of value next to each other
+    pub fn push_slice<T: Copy>(&mut self, count: usize, value: T) -> &mut [T] {
+        unsafe {
+            let pointer = self.push_bytes(size_of::<T>() * count, align_of::<T>()) as *mut T;
+
+            let mut i = 0;
+            while i < count {
+                ptr::write(pointer.add(i), value);
+                i += 1;
+            }
+
+            slice::from_raw_parts_mut(pointer, count)
+        }
+    }
+
+    pub fn push_copy<T: Copy>(&mut self, values: &[T]) -> &mut [T] {
+        unsafe {
+            let pointer = self.push_bytes(size_of_val(values), align_of::<T>()) as *mut T;
+            ptr::copy_nonoverlapping(values.as_ptr(), pointer, values.len());
+            slice::from_raw_parts_mut(pointer, values.len())
+        }
+    }
+
+    // Everything pushed after this can be thrown away with pop_to_mark
+    pub fn mark(&self) -> usize {
+        self.used
+    }
+
+    pub fn pop_to_mark(&mut self, mark: usize) {
+        assert!(mark <= self.used, "Arena mark is ahead of what's been pushed");
+        self.used = mark;
+    }
+
+    // Keeps the high water mark so it tells us what a whole session needed
+    pub fn reset(&mut self) {
+        self.used = 0;
+    }
+
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn high_water_mark(&self) -> usize {
+        self.high_water_mark
+    }
+
+    fn push_bytes(&mut self, size: usize, align: usize) -> *mut u8 {
+        let address = self.base as usize + self.used;
+        let padding = address.next_multiple_of(align) - address;
+
+        assert!(self.used + padding + size <= self.size,
+            "Arena out of memory: {} bytes used, {} more needed, {} total", self.used, padding + size, self.size);
+
+        let pointer = unsafe { self.base.add(self.used + padding) };
+
+        self.used += padding + size;
+        if self.used > self.high_water_mark {
+            self.high_water_mark = self.used;
+        }
+
+        pointer
+    }
+}
@@ -0,0 +1,393 @@
+use crate::{OffscreenBuffer, PixelFormat, Vector2, Vector2i32};
+use std::cmp::{max, min};
+
+// The pixels of an OffscreenBuffer as a plain slice, 0xAARRGGBB each. Everything here takes
+// signed screen coordinates and leaves out whatever falls outside, so callers can hand over
+// geometry that's partly or entirely off screen.
+pub struct Canvas<'a> {
+    pixels: &'a mut [u32],
+    width: u32,
+    height: u32,
+    // Pixels from one row to the next, at least width
+    stride: usize
+}
+
+impl<'a> Canvas<'a> {
+    // The pixels need to be a stride * height slice, stride being at least width
+    pub fn new(pixels: &'a mut [u32], width: u32, height: u32, stride: usize) -> Canvas<'a> {
+        assert!(stride >= width as usize, "Canvas stride {} is less than its width {}", stride, width);
+        assert!(pixels.len() >= stride * height as usize, "Canvas needs {} pixels but only has {}", stride * height as usize, pixels.len());
+
+        Canvas { pixels, width, height, stride }
+    }
+
+    // buffer.memory has to point at pitch * height bytes the canvas gets to itself for 'a
+    pub(crate) unsafe fn from_buffer(buffer: &'a mut OffscreenBuffer) -> Canvas<'a> {
+        assert!(buffer.pixel_format == PixelFormat::Bgra8 && buffer.bytes_per_pixel == 4, "Canvas only draws into 32 bit pixels");
+        assert!(buffer.pitch.is_multiple_of(4), "Buffer pitch {} isn't a whole number of pixels", buffer.pitch);
+
+        let stride = (buffer.pitch / 4) as usize;
+        let pixels = if buffer.memory.is_null() || buffer.height == 0 {
+            &mut []
+        } else {
+            std::slice::from_raw_parts_mut(buffer.memory as *mut u32, stride * buffer.height as usize)
+        };
+
+        Canvas::new(pixels, buffer.width, if pixels.is_empty() { 0 } else { buffer.height }, stride)
+    }
+
+    pub fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn height(&self) -> u32 {
+        self.height
+    }
+
+    // None outside the canvas
+    pub fn get_pixel(&self, x: i32, y: i32) -> Option<u32> {
+        self.index(x, y).map(|index| self.pixels[index])
+    }
+
+    pub fn clear(&mut self, color: u32) {
+        let mut y = 0;
+        while y < self.height as usize {
+            let row = y * self.stride;
+            self.pixels[row..row + self.width as usize].fill(color);
+            y += 1;
+        }
+    }
+
+    // Mixes color over what's there by its alpha, fully opaque colors just replace it
+    pub fn blend_pixel(&mut self, x: i32, y: i32, color: u32) {
+        if let Some(index) = self.index(x, y) {
+            let alpha = get_alpha(color);
+            if alpha == 1.0 {
+                self.pixels[index] = color;
+            } else {
+                self.pixels[index] = lerp_color(self.pixels[index], color, alpha);
+            }
+        }
+    }
+
+    // Everything from top_left up to but not including bottom_right
+    pub fn fill_rectangle(&mut self, top_left: Vector2i32, bottom_right: Vector2i32, color: u32) {
+        let start_x = max(top_left.x, 0);
+        let start_y = max(top_left.y, 0);
+        let end_x = min(bottom_right.x, self.width as i32);
+        let end_y = min(bottom_right.y, self.height as i32);
+
+        let mut y = start_y;
+        while y < end_y {
+            let mut x = start_x;
+            while x < end_x {
+                self.blend_pixel(x, y, color);
+                x += 1;
+            }
+            y += 1;
+        }
+    }
+
+    // Anti-aliased, with Xiaolin Wu's algorithm. Pixel (x, y) covers x to x + 1 and y to y + 1,
+    // so endpoints can sit anywhere inside a pixel. Every pixel the line touches gets color with
+    // its alpha scaled by how much of the pixel the line covers.
+    pub fn draw_line(&mut self, a: Vector2, b: Vector2, color: u32) {
+        // Moved so whole numbers are pixel centers, which is what the algorithm works in
+        let a = Vector2 { x: a.x - 0.5, y: a.y - 0.5 };
+        let b = Vector2 { x: b.x - 0.5, y: b.y - 0.5 };
+
+        // One pixel of margin, the anti-aliased edge of a line just outside the canvas still shows
+        let (a, b) = match clip_line(a, b, -1.0, -1.0, self.width as f32, self.height as f32) {
+            Some(clipped) => clipped,
+            None => return
+        };
+
+        // Walks along whichever axis the line is longer in, one pixel column at a time
+        let steep = (b.y - a.y).abs() > (b.x - a.x).abs();
+        let (mut x0, mut y0, mut x1, mut y1) = if steep {
+            (a.y, a.x, b.y, b.x)
+        } else {
+            (a.x, a.y, b.x, b.y)
+        };
+
+        if x0 > x1 {
+            std::mem::swap(&mut x0, &mut x1);
+            std::mem::swap(&mut y0, &mut y1);
+        }
+
+        let gradient = if x1 - x0 == 0.0 {
+            1.0
+        } else {
+            (y1 - y0) / (x1 - x0)
+        };
+
+        // Each endpoint only covers part of its column, by how far into the column the line starts or ends
+        let x_start = (x0 + 0.5).floor();
+        let y_start = y0 + gradient * (x_start - x0);
+        let start_coverage = 1.0 - fractional_part(x0 + 0.5);
+        self.plot_column(steep, x_start as i32, y_start, start_coverage, color);
+
+        let x_end = (x1 + 0.5).floor();
+        let y_end = y1 + gradient * (x_end - x1);
+        let end_coverage = fractional_part(x1 + 0.5);
+        self.plot_column(steep, x_end as i32, y_end, end_coverage, color);
+
+        let mut y_intersect = y_start + gradient;
+        let mut x = x_start as i32 + 1;
+        while x < x_end as i32 {
+            self.plot_column(steep, x, y_intersect, 1.0, color);
+            y_intersect += gradient;
+            x += 1;
+        }
+    }
+
+    // The line crosses column x at y, which it shares between the two pixels it falls between
+    fn plot_column(&mut self, steep: bool, x: i32, y: f32, coverage: f32, color: u32) {
+        let y_pixel = y.floor();
+        let y_fraction = y - y_pixel;
+        let y_pixel = y_pixel as i32;
+
+        if steep {
+            self.blend_pixel_coverage(y_pixel, x, color, (1.0 - y_fraction) * coverage);
+            self.blend_pixel_coverage(y_pixel + 1, x, color, y_fraction * coverage);
+        } else {
+            self.blend_pixel_coverage(x, y_pixel, color, (1.0 - y_fraction) * coverage);
+            self.blend_pixel_coverage(x, y_pixel + 1, color, y_fraction * coverage);
+        }
+    }
+
+    // Like blend_pixel with color's alpha scaled by coverage, 0 to 1
+    pub(crate) fn blend_pixel_coverage(&mut self, x: i32, y: i32, color: u32, coverage: f32) {
+        if let Some(index) = self.index(x, y) {
+            let alpha = get_alpha(color) * coverage;
+            if alpha >= 1.0 {
+                self.pixels[index] = color;
+            } else if alpha > 0.0 {
+                self.pixels[index] = lerp_color(self.pixels[index], color, alpha);
+            }
+        }
+    }
+
+    // TODO: Make it so anti-aliasing works properly with transparent circles
+    // Solid inside radius, with a one pixel anti-aliased edge
+    pub fn fill_circle(&mut self, center: Vector2i32, radius: i32, color: u32) {
+        let start_x = max(center.x - radius, 0);
+        let start_y = max(center.y - radius, 0);
+        let end_x = min(center.x + radius, self.width as i32 - 1);
+        let end_y = min(center.y + radius, self.height as i32 - 1);
+
+        let mut x = start_x;
+        while x <= end_x {
+            let mut y = start_y;
+            while y <= end_y {
+                let dx = (x - center.x) as f32;
+                let dy = (y - center.y) as f32;
+                let dist = (dx * dx + dy * dy).sqrt();
+
+                if dist <= radius as f32 {
+                    self.blend_pixel(x, y, color);
+                } else if dist <= radius as f32 + 1.0 {
+                    let dist_dec = dist - (dist as i32) as f32;
+                    let alpha = ((1.0 - dist_dec) * 255.0) as u32;
+                    let color_with_alpha = (color & 0x00FFFFFF) | (alpha << 24);
+                    self.blend_pixel(x, y, color_with_alpha);
+                }
+                y += 1;
+            }
+            x += 1;
+        }
+    }
+
+    fn index(&self, x: i32, y: i32) -> Option<usize> {
+        if x < 0 || y < 0 || x >= self.width as i32 || y >= self.height as i32 {
+            return None;
+        }
+
+        Some(y as usize * self.stride + x as usize)
+    }
+}
+
+fn fractional_part(value: f32) -> f32 {
+    value - value.floor()
+}
+
+// Liang-Barsky. The part of the line from a to b inside the rectangle, None if it misses it entirely.
+fn clip_line(a: Vector2, b: Vector2, min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Option<(Vector2, Vector2)> {
+    // Whatever a NaN or infinity would draw, it's nothing useful
+    if !(a.x.is_finite() && a.y.is_finite() && b.x.is_finite() && b.y.is_finite()) {
+        return None;
+    }
+
+    let dx = b.x - a.x;
+    let dy = b.y - a.y;
+
+    // How far along the line it enters and leaves the rectangle, 0 at a and 1 at b
+    let mut t_enter: f32 = 0.0;
+    let mut t_leave: f32 = 1.0;
+
+    // Direction against each edge's inward normal, and distance from a to the edge
+    let edges = [(-dx, a.x - min_x), (dx, max_x - a.x), (-dy, a.y - min_y), (dy, max_y - a.y)];
+    for (direction, distance) in edges {
+        if direction == 0.0 {
+            // Parallel to the edge, so either entirely inside of it or entirely outside
+            if distance < 0.0 {
+                return None;
+            }
+        } else {
+            let t = distance / direction;
+            if direction < 0.0 {
+                t_enter = t_enter.max(t);
+            } else {
+                t_leave = t_leave.min(t);
+            }
+        }
+    }
+
+    if t_enter > t_leave {
+        return None;
+    }
+
+    Some((
+        Vector2 { x: a.x + dx * t_enter, y: a.y + dy * t_enter },
+        Vector2 { x: a.x + dx * t_leave, y: a.y + dy * t_leave }
+    ))
+}
+
+fn get_alpha(color: u32) -> f32 {
+    (color >> 24) as f32 / 255.0
+}
+
+fn lerp_color(a: u32, b: u32, t: f32) -> u32 {
+    let a_red = (a >> 16) as u8;
+    let a_green = (a >> 8) as u8;
+    let a_blue = a as u8;
+
+    let b_red = (b >> 16) as u8;
+    let b_green = (b >> 8) as u8;
+    let b_blue = b as u8;
+
+    let red = (a_red as f32 + t * (b_red as f32 - a_red as f32)) as u8;
+    let green = (a_green as f32 + t * (b_green as f32 - a_green as f32)) as u8;
+    let blue = (a_blue as f32 + t * (b_blue as f32 - a_blue as f32)) as u8;
+
+    // NOTE: This version uses fewer variables, if a performance concern
+    //let red = (a >> 16) as u8 + (t * ((b >> 16) as u8 - (a >> 16) as u8) as f32) as u8;
+    //let green = (a >> 8) as u8 + (t * ((b >> 8) as u8 - (a >> 8) as u8) as f32) as u8;
+    //let blue = a as u8 + (t * (b as u8 - a as u8) as f32) as u8;
+
+    ((red as u32) << 16) | ((green as u32) << 8) | blue as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: u32 = 64;
+    // Blending truncates to a whole channel value, so each pixel can come out one below
+    const PIXEL_TOLERANCE: f32 = 1.5 / 255.0;
+
+    // How much of each pixel an opaque white line over black covers, 0 to 1
+    fn line_coverage(a: Vector2, b: Vector2) -> Vec<f32> {
+        let mut pixels = vec![0u32; (SIZE * SIZE) as usize];
+        let mut canvas = Canvas::new(&mut pixels, SIZE, SIZE, SIZE as usize);
+        canvas.draw_line(a, b, 0xFFFFFFFF);
+        pixels.iter().map(|pixel| ((pixel >> 16) & 0xFF) as f32 / 255.0).collect()
+    }
+
+    fn at(coverage: &[f32], x: u32, y: u32) -> f32 {
+        coverage[(y * SIZE + x) as usize]
+    }
+
+    #[test]
+    fn every_column_of_a_shallow_line_adds_up_to_one() {
+        let a = Vector2 { x: 4.3, y: 10.6 };
+        let b = Vector2 { x: 55.8, y: 31.2 };
+        let coverage = line_coverage(a, b);
+        let column = |x: u32| (0..SIZE).map(|y| at(&coverage, x, y)).sum::<f32>();
+
+        let mut x = 5;
+        while x < 55 {
+            assert!((column(x) - 1.0).abs() <= 2.0 * PIXEL_TOLERANCE, "column {} adds up to {}", x, column(x));
+            x += 1;
+        }
+
+        // The end columns only as much as the line reaches into them
+        assert!((column(4) - 0.7).abs() <= 2.0 * PIXEL_TOLERANCE, "first column adds up to {}", column(4));
+        assert!((column(55) - 0.8).abs() <= 2.0 * PIXEL_TOLERANCE, "last column adds up to {}", column(55));
+        assert_eq!(column(3), 0.0);
+        assert_eq!(column(56), 0.0);
+    }
+
+    #[test]
+    fn every_row_of_a_steep_line_adds_up_to_one() {
+        let coverage = line_coverage(Vector2 { x: 30.2, y: 2.5 }, Vector2 { x: 12.9, y: 61.5 });
+        let row = |y: u32| (0..SIZE).map(|x| at(&coverage, x, y)).sum::<f32>();
+
+        let mut y = 3;
+        while y < 61 {
+            assert!((row(y) - 1.0).abs() <= 2.0 * PIXEL_TOLERANCE, "row {} adds up to {}", y, row(y));
+            y += 1;
+        }
+    }
+
+    #[test]
+    fn a_line_through_pixel_centers_only_covers_its_row() {
+        let coverage = line_coverage(Vector2 { x: 10.5, y: 5.5 }, Vector2 { x: 20.5, y: 5.5 });
+
+        let mut x = 0;
+        while x < SIZE {
+            let expected = match x {
+                10 | 20 => 0.5,
+                11..=19 => 1.0,
+                _ => 0.0
+            };
+            assert!((at(&coverage, x, 5) - expected).abs() <= PIXEL_TOLERANCE, "pixel {} is {}", x, at(&coverage, x, 5));
+            assert_eq!(at(&coverage, x, 4), 0.0);
+            assert_eq!(at(&coverage, x, 6), 0.0);
+            x += 1;
+        }
+    }
+
+    // Mirrored, turned around and swapping x and y, a line covers the mirrored pixels just as much
+    #[test]
+    fn lines_are_the_same_in_every_octant() {
+        let lines = [
+            (Vector2 { x: 4.3, y: 10.6 }, Vector2 { x: 55.8, y: 31.2 }),
+            (Vector2 { x: 7.7, y: 8.1 }, Vector2 { x: 40.2, y: 39.9 }),
+            (Vector2 { x: 20.0, y: 33.25 }, Vector2 { x: 21.5, y: 33.75 })
+        ];
+
+        for (a, b) in lines {
+            let expected = line_coverage(a, b);
+
+            let mut octant = 0;
+            while octant < 16 {
+                let (mirror_x, mirror_y, transpose, reverse) = (octant & 1 != 0, octant & 2 != 0, octant & 4 != 0, octant & 8 != 0);
+
+                let point = |point: Vector2| {
+                    let x = if mirror_x { SIZE as f32 - point.x } else { point.x };
+                    let y = if mirror_y { SIZE as f32 - point.y } else { point.y };
+                    if transpose { Vector2 { x: y, y: x } } else { Vector2 { x, y } }
+                };
+                let coverage = if reverse { line_coverage(point(b), point(a)) } else { line_coverage(point(a), point(b)) };
+
+                let mut y = 0;
+                while y < SIZE {
+                    let mut x = 0;
+                    while x < SIZE {
+                        let mirrored_x = if mirror_x { SIZE - 1 - x } else { x };
+                        let mirrored_y = if mirror_y { SIZE - 1 - y } else { y };
+                        let (mirrored_x, mirrored_y) = if transpose { (mirrored_y, mirrored_x) } else { (mirrored_x, mirrored_y) };
+
+                        let difference = (at(&coverage, mirrored_x, mirrored_y) - at(&expected, x, y)).abs();
+                        assert!(difference <= PIXEL_TOLERANCE, "line from {:?} to {:?} in octant {} differs by {} at {}, {}",
+                            a, b, octant, difference, x, y);
+                        x += 1;
+                    }
+                    y += 1;
+                }
+                octant += 1;
+            }
+        }
+    }
+}
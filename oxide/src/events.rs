@@ -0,0 +1,91 @@
+use crate::{Key, Vector2i32};
+
+// Plenty for one frame, even with a fast mouse. Anything past this is counted in dropped_count.
+pub const MAX_INPUT_EVENTS: usize = 128;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum InputEvent {
+    // Only when the key goes down, holding it doesn't repeat these
+    KeyDown(Key),
+    KeyUp(Key),
+    // Client area coordinates, same as MouseState::pos
+    MouseMove(Vector2i32),
+    MouseButtonDown(MouseButton),
+    MouseButtonUp(MouseButton),
+    // Same units as MouseState::wheel_delta
+    Wheel(i16),
+    // Text typed, after the OS applied the keyboard layout
+    Text(char)
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TimedInputEvent {
+    // Milliseconds on the PlatformServices::now clock, when the platform picked the event up
+    pub time: f64,
+    pub event: InputEvent
+}
+
+// Everything that happened since the last frame, in order. The ButtonState snapshot only says
+// where things ended up, this also has the clicks that went down and up within a single frame.
+// Fixed size so InputController stays plain data the host can copy and record.
+#[derive(Clone, Copy)]
+pub struct InputEvents {
+    count: u32,
+    dropped_count: u32,
+    events: [TimedInputEvent; MAX_INPUT_EVENTS]
+}
+
+impl Default for InputEvents {
+    fn default() -> Self {
+        InputEvents {
+            count: 0,
+            dropped_count: 0,
+            events: [TimedInputEvent { time: 0.0, event: InputEvent::Wheel(0) }; MAX_INPUT_EVENTS]
+        }
+    }
+}
+
+impl InputEvents {
+    pub fn push(&mut self, time: f64, event: InputEvent) {
+        if self.count as usize == MAX_INPUT_EVENTS {
+            self.dropped_count += 1;
+            return;
+        }
+
+        self.events[self.count as usize] = TimedInputEvent { time, event };
+        self.count += 1;
+    }
+
+    pub fn clear(&mut self) {
+        self.count = 0;
+        self.dropped_count = 0;
+    }
+
+    pub fn as_slice(&self) -> &[TimedInputEvent] {
+        &self.events[..self.count as usize]
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, TimedInputEvent> {
+        self.as_slice().iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.count as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    // Events that didn't fit this frame
+    pub fn dropped_count(&self) -> u32 {
+        self.dropped_count
+    }
+}
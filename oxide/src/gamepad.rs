@@ -0,0 +1,93 @@
+use crate::{ButtonState, Vector2};
+
+pub const MAX_GAMEPADS: usize = 4;
+
+// XInput's recommended deadzones, as a fraction of the full stick range.
+// The Linux joystick driver reports in the same range so they work there too.
+pub const LEFT_STICK_DEADZONE: f32 = 7849.0 / 32767.0;
+pub const RIGHT_STICK_DEADZONE: f32 = 8689.0 / 32767.0;
+pub const TRIGGER_THRESHOLD: f32 = 30.0 / 255.0;
+
+// Named after the Xbox layout. The discriminant indexes GamepadState::buttons, so Count stays last.
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum GamepadButton {
+    A,
+    B,
+    X,
+    Y,
+    LeftShoulder,
+    RightShoulder,
+    Back,
+    Start,
+    LeftThumb,
+    RightThumb,
+    DpadUp,
+    DpadDown,
+    DpadLeft,
+    DpadRight,
+    Count
+}
+
+pub const GAMEPAD_BUTTON_COUNT: usize = GamepadButton::Count as usize;
+
+#[derive(Clone, Copy, Default)]
+pub struct GamepadState {
+    pub is_connected: bool,
+    // -1 to 1 with the deadzone taken out, positive y is up
+    pub left_stick: Vector2,
+    pub right_stick: Vector2,
+    // 0 to 1 with the threshold taken out
+    pub left_trigger: f32,
+    pub right_trigger: f32,
+    buttons: [ButtonState; GAMEPAD_BUTTON_COUNT]
+}
+
+impl GamepadState {
+    pub fn button(&self, button: GamepadButton) -> ButtonState {
+        self.buttons[button as usize]
+    }
+
+    pub fn set_is_down(&mut self, button: GamepadButton, is_down: bool) {
+        self.buttons[button as usize].is_down = is_down;
+    }
+
+    // Moves every button's is_down into was_down and takes everything else from new_state
+    pub fn update(&mut self, new_state: &GamepadState) {
+        self.is_connected = new_state.is_connected;
+        self.left_stick = new_state.left_stick;
+        self.right_stick = new_state.right_stick;
+        self.left_trigger = new_state.left_trigger;
+        self.right_trigger = new_state.right_trigger;
+
+        let mut i = 0;
+        while i < GAMEPAD_BUTTON_COUNT {
+            self.buttons[i].update(new_state.buttons[i].is_down);
+            i += 1;
+        }
+    }
+}
+
+// x and y from -1 to 1. Radial so diagonals aren't cut off, and rescaled so
+// the stick still goes smoothly from 0 right at the edge of the deadzone.
+pub fn apply_stick_deadzone(x: f32, y: f32, deadzone: f32) -> Vector2 {
+    let length = (x * x + y * y).sqrt();
+    if length <= deadzone {
+        return Vector2 { x: 0.0, y: 0.0 };
+    }
+
+    let scaled_length = ((length - deadzone) / (1.0 - deadzone)).min(1.0);
+    Vector2 {
+        x: x / length * scaled_length,
+        y: y / length * scaled_length
+    }
+}
+
+// value from 0 to 1
+pub fn apply_trigger_threshold(value: f32) -> f32 {
+    if value <= TRIGGER_THRESHOLD {
+        0.0
+    } else {
+        ((value - TRIGGER_THRESHOLD) / (1.0 - TRIGGER_THRESHOLD)).min(1.0)
+    }
+}
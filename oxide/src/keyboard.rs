@@ -0,0 +1,143 @@
+use crate::ButtonState;
+
+// Keys the platforms translate their own key codes into, by position on a US layout.
+// The discriminant indexes KeyboardState, so Unknown has to stay last.
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Key {
+    A, B, C, D, E, F, G, H, I, J, K, L, M,
+    N, O, P, Q, R, S, T, U, V, W, X, Y, Z,
+    Num0, Num1, Num2, Num3, Num4, Num5, Num6, Num7, Num8, Num9,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Up, Left, Down, Right,
+    Escape, Tab, Space, Enter, Backspace,
+    Insert, Delete, Home, End, PageUp, PageDown,
+    LeftShift, RightShift, LeftControl, RightControl, LeftAlt, RightAlt,
+    Minus, Equals, LeftBracket, RightBracket, Backslash,
+    Semicolon, Apostrophe, Comma, Period, Slash, Grave,
+    // Anything the platform has no translation for
+    Unknown
+}
+
+pub const KEY_COUNT: usize = Key::Unknown as usize + 1;
+
+const LETTERS: [Key; 26] = [
+    Key::A, Key::B, Key::C, Key::D, Key::E, Key::F, Key::G, Key::H, Key::I, Key::J, Key::K, Key::L, Key::M,
+    Key::N, Key::O, Key::P, Key::Q, Key::R, Key::S, Key::T, Key::U, Key::V, Key::W, Key::X, Key::Y, Key::Z
+];
+
+const DIGITS: [Key; 10] = [
+    Key::Num0, Key::Num1, Key::Num2, Key::Num3, Key::Num4, Key::Num5, Key::Num6, Key::Num7, Key::Num8, Key::Num9
+];
+
+const FUNCTION_KEYS: [Key; 12] = [
+    Key::F1, Key::F2, Key::F3, Key::F4, Key::F5, Key::F6, Key::F7, Key::F8, Key::F9, Key::F10, Key::F11, Key::F12
+];
+
+impl Key {
+    // Both platforms number these in order, so they only need the offset from the first one
+    pub fn letter(index: u32) -> Key {
+        LETTERS.get(index as usize).copied().unwrap_or(Key::Unknown)
+    }
+
+    pub fn digit(index: u32) -> Key {
+        DIGITS.get(index as usize).copied().unwrap_or(Key::Unknown)
+    }
+
+    // F1 is 1
+    pub fn function(number: u32) -> Key {
+        match number {
+            0 => Key::Unknown,
+            _ => FUNCTION_KEYS.get(number as usize - 1).copied().unwrap_or(Key::Unknown)
+        }
+    }
+}
+
+// Names for the keys that aren't letters, digits or F keys, as used in the bindings file
+const KEY_NAMES: [(&str, Key); 32] = [
+    ("Up", Key::Up), ("Left", Key::Left), ("Down", Key::Down), ("Right", Key::Right),
+    ("Escape", Key::Escape), ("Tab", Key::Tab), ("Space", Key::Space), ("Enter", Key::Enter), ("Backspace", Key::Backspace),
+    ("Insert", Key::Insert), ("Delete", Key::Delete), ("Home", Key::Home), ("End", Key::End),
+    ("PageUp", Key::PageUp), ("PageDown", Key::PageDown),
+    ("LeftShift", Key::LeftShift), ("RightShift", Key::RightShift), ("LeftControl", Key::LeftControl),
+    ("RightControl", Key::RightControl), ("LeftAlt", Key::LeftAlt), ("RightAlt", Key::RightAlt),
+    ("Minus", Key::Minus), ("Equals", Key::Equals), ("LeftBracket", Key::LeftBracket), ("RightBracket", Key::RightBracket),
+    ("Backslash", Key::Backslash), ("Semicolon", Key::Semicolon), ("Apostrophe", Key::Apostrophe),
+    ("Comma", Key::Comma), ("Period", Key::Period), ("Slash", Key::Slash), ("Grave", Key::Grave)
+];
+
+impl Key {
+    // "W", "7", "F5", "PageUp", case sensitive like the rest of the bindings file
+    pub fn from_name(name: &str) -> Option<Key> {
+        let bytes = name.as_bytes();
+
+        if bytes.len() == 1 && bytes[0].is_ascii_uppercase() {
+            return Some(Key::letter((bytes[0] - b'A') as u32));
+        }
+        if bytes.len() == 1 && bytes[0].is_ascii_digit() {
+            return Some(Key::digit((bytes[0] - b'0') as u32));
+        }
+        if let Some(Ok(number)) = name.strip_prefix('F').map(|number| number.parse::<u32>()) {
+            return match Key::function(number) {
+                Key::Unknown => None,
+                key => Some(key)
+            };
+        }
+
+        KEY_NAMES.iter().find(|(key_name, _)| *key_name == name).map(|(_, key)| *key)
+    }
+}
+
+// The state of every key, the platform sets is_down and InputController::update the rest
+#[derive(Clone, Copy)]
+pub struct KeyboardState {
+    keys: [ButtonState; KEY_COUNT]
+}
+
+impl Default for KeyboardState {
+    fn default() -> Self {
+        KeyboardState {
+            keys: [ButtonState::default(); KEY_COUNT]
+        }
+    }
+}
+
+impl KeyboardState {
+    pub fn get(&self, key: Key) -> ButtonState {
+        self.keys[key as usize]
+    }
+
+    // Returns whether the key was already down, to tell repeats apart from presses
+    pub fn set_is_down(&mut self, key: Key, is_down: bool) -> bool {
+        let was_down = self.keys[key as usize].is_down;
+        self.keys[key as usize].is_down = is_down;
+        was_down
+    }
+
+    // Moves every key's is_down into was_down and takes is_down from new_state
+    pub fn update(&mut self, new_state: &KeyboardState) {
+        let mut i = 0;
+        while i < KEY_COUNT {
+            self.keys[i].update(new_state.keys[i].is_down);
+            i += 1;
+        }
+    }
+}
+
+// Either side counts
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool
+}
+
+impl Modifiers {
+    pub fn from_keyboard(keyboard: &KeyboardState) -> Modifiers {
+        Modifiers {
+            shift: keyboard.get(Key::LeftShift).is_down || keyboard.get(Key::RightShift).is_down,
+            control: keyboard.get(Key::LeftControl).is_down || keyboard.get(Key::RightControl).is_down,
+            alt: keyboard.get(Key::LeftAlt).is_down || keyboard.get(Key::RightAlt).is_down
+        }
+    }
+}
@@ -0,0 +1,253 @@
+use crate::*;
+use std::mem::{align_of, offset_of, size_of};
+
+// Bump when something crosses the host/library boundary in a way the sizes and offsets
+// below can't see, e.g. a field changing type but not size
+pub const LAYOUT_VERSION: u32 = 1;
+
+// Describes the structs the host and the game library pass to each other.
+// GameState isn't one of them, it only ever lives inside GameMemory.
+// The host compares its own against the library's on every load,
+// a mismatch means they disagree on where things are in memory.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LayoutFingerprint {
+    pub version: u32,
+    pub game_memory_size: u32,
+    pub input_controller_size: u32,
+    pub offscreen_buffer_size: u32,
+    // Hash of every field's name, offset and size
+    pub field_hash: u64
+}
+
+impl LayoutFingerprint {
+    // Human readable list of what differs, for the host to report
+    pub fn differences(&self, other: &LayoutFingerprint) -> Vec<String> {
+        let mut differences = Vec::new();
+
+        if self.version != other.version {
+            differences.push(format!("version {} vs {}", self.version, other.version));
+        }
+        if self.game_memory_size != other.game_memory_size {
+            differences.push(format!("GameMemory size {} vs {}", self.game_memory_size, other.game_memory_size));
+        }
+        if self.input_controller_size != other.input_controller_size {
+            differences.push(format!("InputController size {} vs {}", self.input_controller_size, other.input_controller_size));
+        }
+        if self.offscreen_buffer_size != other.offscreen_buffer_size {
+            differences.push(format!("OffscreenBuffer size {} vs {}", self.offscreen_buffer_size, other.offscreen_buffer_size));
+        }
+        if self.field_hash != other.field_hash {
+            differences.push(format!("field hash {:016x} vs {:016x}", self.field_hash, other.field_hash));
+        }
+
+        differences
+    }
+}
+
+// Adds a field to the hash, with the size of its type so nested structs changing show up too
+macro_rules! hash_field {
+    ($hash:expr, $type:ty, $field:ident) => {{
+        let field_size = {
+            let value: Option<&$type> = None;
+            size_of_field(value.map(|value| &value.$field))
+        };
+        hash_layout_entry($hash, stringify!($type), stringify!($field), offset_of!($type, $field), field_size)
+    }};
+}
+
+// Has to be updated whenever a field is added to one of these structs
+pub fn layout_fingerprint() -> LayoutFingerprint {
+    let mut hash = FNV_OFFSET_BASIS;
+
+    hash = hash_field!(hash, GameMemory, is_initialized);
+    hash = hash_field!(hash, GameMemory, permanent_storage_size);
+    hash = hash_field!(hash, GameMemory, permanent_storage);
+    hash = hash_field!(hash, GameMemory, transient_storage_size);
+    hash = hash_field!(hash, GameMemory, transient_storage);
+    hash = hash_field!(hash, GameMemory, services);
+    hash = hash_field!(hash, PlatformServices, get_file_size);
+    hash = hash_field!(hash, PlatformServices, read_entire_file);
+    hash = hash_field!(hash, PlatformServices, write_entire_file);
+    hash = hash_field!(hash, PlatformServices, log);
+    hash = hash_field!(hash, PlatformServices, now);
+    hash = hash_field!(hash, PlatformServices, set_relative_mouse);
+    hash = hash_type(hash, "GameMemory", size_of::<GameMemory>(), align_of::<GameMemory>());
+
+    hash = hash_field!(hash, InputController, delta_time);
+    hash = hash_field!(hash, InputController, mouse_state);
+    hash = hash_field!(hash, InputController, keyboard);
+    hash = hash_field!(hash, InputController, modifiers);
+    hash = hash_field!(hash, InputController, gamepads);
+    hash = hash_field!(hash, InputController, events);
+    hash = hash_type(hash, "InputController", size_of::<InputController>(), align_of::<InputController>());
+
+    hash = hash_field!(hash, SoundOutputBuffer, samples_per_second);
+    hash = hash_field!(hash, SoundOutputBuffer, channel_count);
+    hash = hash_field!(hash, SoundOutputBuffer, sample_count);
+    hash = hash_field!(hash, SoundOutputBuffer, samples);
+    hash = hash_type(hash, "SoundOutputBuffer", size_of::<SoundOutputBuffer>(), align_of::<SoundOutputBuffer>());
+
+    hash = hash_field!(hash, GameApi, version);
+    hash = hash_field!(hash, GameApi, init);
+    hash = hash_field!(hash, GameApi, update);
+    hash = hash_field!(hash, GameApi, render);
+    hash = hash_field!(hash, GameApi, get_sound_samples);
+    hash = hash_field!(hash, GameApi, on_reload);
+    hash = hash_field!(hash, GameApi, shutdown);
+    hash = hash_type(hash, "GameApi", size_of::<GameApi>(), align_of::<GameApi>());
+
+    hash = hash_field!(hash, OffscreenBuffer, memory);
+    hash = hash_field!(hash, OffscreenBuffer, width);
+    hash = hash_field!(hash, OffscreenBuffer, height);
+    hash = hash_field!(hash, OffscreenBuffer, pitch);
+    hash = hash_field!(hash, OffscreenBuffer, bytes_per_pixel);
+    hash = hash_field!(hash, OffscreenBuffer, pixel_format);
+    hash = hash_type(hash, "OffscreenBuffer", size_of::<OffscreenBuffer>(), align_of::<OffscreenBuffer>());
+
+    hash = hash_field!(hash, WindowDimensions, width);
+    hash = hash_field!(hash, WindowDimensions, height);
+    hash = hash_type(hash, "WindowDimensions", size_of::<WindowDimensions>(), align_of::<WindowDimensions>());
+
+    LayoutFingerprint {
+        version: LAYOUT_VERSION,
+        game_memory_size: size_of::<GameMemory>() as u32,
+        input_controller_size: size_of::<InputController>() as u32,
+        offscreen_buffer_size: size_of::<OffscreenBuffer>() as u32,
+        field_hash: hash
+    }
+}
+
+// Up to how many fields GameState can have, GameStateHeader keeps a hash for each
+pub(crate) const MAX_GAME_STATE_FIELDS: usize = 32;
+
+// Where everything in GameState is, field by field in the order they're laid out. GameState is kept
+// across reloads, so the library compares this with what the state was written with before trusting it.
+// Each hash covers its field and every one before it, so the fields in front of the first one that
+// changed can be kept as they are. Has to be updated whenever a field is added to GameState.
+pub(crate) struct GameStateLayout {
+    pub field_count: usize,
+    pub hashes: [u64; MAX_GAME_STATE_FIELDS],
+    // Where each field ends, everything before it is laid out the way its hash says
+    pub ends: [usize; MAX_GAME_STATE_FIELDS]
+}
+
+impl GameStateLayout {
+    // Hashes the field along with whatever type_hash says about the structs inside it
+    fn push(&mut self, type_name: &str, field_name: &str, offset: usize, size: usize, type_hash: u64) {
+        assert!(self.field_count < MAX_GAME_STATE_FIELDS, "GameState has more than {} fields", MAX_GAME_STATE_FIELDS);
+
+        let previous = if self.field_count == 0 { FNV_OFFSET_BASIS } else { self.hashes[self.field_count - 1] };
+        let hash = hash_layout_entry(previous, type_name, field_name, offset, size);
+        self.hashes[self.field_count] = hash_bytes(hash, &type_hash.to_le_bytes());
+        self.ends[self.field_count] = offset + size;
+        self.field_count += 1;
+    }
+}
+
+macro_rules! game_state_field {
+    ($layout:expr, $field:ident) => {
+        game_state_field!($layout, $field, 0)
+    };
+    ($layout:expr, $field:ident, $type_hash:expr) => {{
+        let field_size = {
+            let value: Option<&GameState> = None;
+            size_of_field(value.map(|value| &value.$field))
+        };
+        $layout.push("GameState", stringify!($field), offset_of!(GameState, $field), field_size, $type_hash)
+    }};
+}
+
+pub(crate) fn game_state_layout() -> GameStateLayout {
+    let mut layout = GameStateLayout {
+        field_count: 0,
+        hashes: [0; MAX_GAME_STATE_FIELDS],
+        ends: [0; MAX_GAME_STATE_FIELDS]
+    };
+
+    // The structs inside it can have their fields moved around too
+    let mut camera = FNV_OFFSET_BASIS;
+    camera = hash_field!(camera, Camera, x);
+    camera = hash_field!(camera, Camera, y);
+    camera = hash_field!(camera, Camera, width);
+    camera = hash_field!(camera, Camera, height);
+    camera = hash_field!(camera, Camera, y_scale);
+
+    let mut curves = FNV_OFFSET_BASIS;
+    curves = hash_field!(curves, BezierCurve, p0);
+    curves = hash_field!(curves, BezierCurve, p1);
+    curves = hash_field!(curves, BezierCurve, p2);
+    curves = hash_field!(curves, BezierCurve, p3);
+    curves = hash_type(curves, "Option<BezierCurve>", size_of::<Option<BezierCurve>>(), align_of::<Option<BezierCurve>>());
+
+    let mut undo_history = curves;
+    undo_history = hash_field!(undo_history, UndoHistory, count);
+    undo_history = hash_field!(undo_history, UndoHistory, next);
+    undo_history = hash_field!(undo_history, UndoHistory, entries);
+
+    let mut input_mapping = FNV_OFFSET_BASIS;
+    input_mapping = hash_field!(input_mapping, InputMapping, bindings);
+    input_mapping = hash_field!(input_mapping, InputMapping, actions);
+    input_mapping = hash_type(input_mapping, "Binding", size_of::<Binding>(), align_of::<Binding>());
+    input_mapping = hash_type(input_mapping, "ActionState", size_of::<ActionState>(), align_of::<ActionState>());
+    // Bindings hold actions, keys and buttons as their discriminants, which mean something else
+    // or nothing at all once variants are added or removed
+    input_mapping = hash_bytes(input_mapping, &(ACTION_COUNT as u64).to_le_bytes());
+    input_mapping = hash_bytes(input_mapping, &(KEY_COUNT as u64).to_le_bytes());
+    input_mapping = hash_bytes(input_mapping, &(GAMEPAD_BUTTON_COUNT as u64).to_le_bytes());
+
+    let arena = hash_type(FNV_OFFSET_BASIS, "MemoryArena", size_of::<MemoryArena>(), align_of::<MemoryArena>());
+
+    game_state_field!(layout, curves, curves);
+    game_state_field!(layout, curve_fill_colors);
+    game_state_field!(layout, camera, camera);
+    game_state_field!(layout, target_camera_height);
+    game_state_field!(layout, undo_history, undo_history);
+    game_state_field!(layout, selected_curve_index);
+    game_state_field!(layout, selected_control_point);
+    game_state_field!(layout, zoom_around_center);
+    game_state_field!(layout, previous_camera, camera);
+    game_state_field!(layout, delta_time);
+    game_state_field!(layout, last_perf_print);
+    game_state_field!(layout, last_render_time);
+    game_state_field!(layout, input_mapping, input_mapping);
+    game_state_field!(layout, transient_arena, arena);
+
+    layout
+}
+
+// Exported so the host can ask a freshly loaded library what layout it was built with
+#[no_mangle]
+pub extern "C" fn game_layout_fingerprint() -> LayoutFingerprint {
+    layout_fingerprint()
+}
+
+pub(crate) const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn size_of_field<T>(_field: Option<&T>) -> usize {
+    size_of::<T>()
+}
+
+fn hash_layout_entry(hash: u64, type_name: &str, field_name: &str, offset: usize, size: usize) -> u64 {
+    let mut hash = hash_bytes(hash, type_name.as_bytes());
+    hash = hash_bytes(hash, field_name.as_bytes());
+    hash = hash_bytes(hash, &(offset as u64).to_le_bytes());
+    hash_bytes(hash, &(size as u64).to_le_bytes())
+}
+
+fn hash_type(hash: u64, type_name: &str, size: usize, align: usize) -> u64 {
+    let mut hash = hash_bytes(hash, type_name.as_bytes());
+    hash = hash_bytes(hash, &(size as u64).to_le_bytes());
+    hash_bytes(hash, &(align as u64).to_le_bytes())
+}
+
+// FNV-1a, stable across builds unlike std's DefaultHasher
+pub(crate) fn hash_bytes(hash: u64, bytes: &[u8]) -> u64 {
+    let mut hash = hash;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(FNV_PRIME);
+    }
+    hash
+}
@@ -2,8 +2,36 @@ use std::ffi::c_void;
 use std::ptr;
 use std::cmp::min;
 use std::cmp::max;
-use windows::Win32::Graphics::Gdi::BITMAPINFO;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::mem::{align_of, size_of};
+
+mod actions;
+mod api;
+mod arena;
+mod canvas;
+mod events;
+mod gamepad;
+mod keyboard;
+mod layout;
+mod path;
+mod raster;
+mod services;
+mod stroke;
+#[cfg(feature = "test-patterns")]
+mod test_patterns;
+pub use actions::*;
+pub use api::*;
+pub use arena::*;
+pub use canvas::*;
+pub use events::*;
+pub use gamepad::*;
+pub use keyboard::*;
+pub use layout::*;
+pub use path::*;
+pub use raster::*;
+pub use services::*;
+pub use stroke::*;
+#[cfg(feature = "test-patterns")]
+pub use test_patterns::*;
 
 #[derive(Clone, Copy, Default)]
 pub struct ButtonState {
@@ -11,85 +39,216 @@ pub struct ButtonState {
     pub was_down: bool
 }
 
+impl ButtonState {
+    // Went down this frame
+    pub fn pressed(&self) -> bool {
+        self.is_down && !self.was_down
+    }
+
+    // Went up this frame
+    pub fn released(&self) -> bool {
+        !self.is_down && self.was_down
+    }
+
+    pub fn held(&self) -> bool {
+        self.is_down
+    }
+
+    pub fn update(&mut self, is_down: bool) {
+        self.was_down = self.is_down;
+        self.is_down = is_down;
+    }
+}
+
 #[derive(Clone, Copy, Default)]
 pub struct MouseState {
-    pub pos: Vector2u32,
-    pub prev_pos: Vector2u32,
+    // Client area coordinates. Negative or past the window size while a drag has the mouse captured.
+    // Stays put in relative mode, the movement only shows up in delta then.
+    pub pos: Vector2i32,
+    pub prev_pos: Vector2i32,
+    // How far the mouse moved this frame, in pixels. Keeps going in relative mode.
+    pub delta: Vector2i32,
     pub left: ButtonState,
     pub right: ButtonState,
     pub middle: ButtonState,
+    // Wheel movement this frame, 120 per notch and positive away from the user
     pub wheel_delta: i16
 }
 
 #[derive(Clone, Copy, Default)]
 pub struct InputController {
+    // Milliseconds since the last frame, set by the platform
+    pub delta_time: f32,
     pub mouse_state: MouseState,
-    pub w: ButtonState,
-    pub a: ButtonState,
-    pub s: ButtonState,
-    pub d: ButtonState,
-    pub up: ButtonState,
-    pub left: ButtonState,
-    pub down: ButtonState,
-    pub right: ButtonState,
-    pub esc: ButtonState
+    pub keyboard: KeyboardState,
+    // Worked out from the keyboard in update, the platform doesn't need to set them
+    pub modifiers: Modifiers,
+    // Slots stay put while connected, so a pad keeps its index until it's unplugged
+    pub gamepads: [GamepadState; MAX_GAMEPADS],
+    // What happened during the frame in order, the fields above only hold where it ended up
+    pub events: InputEvents
 }
 
 impl InputController {
-    // Makes all button states was_down and replaces is_down with new_input
+    // Makes all button states was_down and replaces is_down with new_input.
+    // Events are only ever for one frame so they're taken over as they are.
     pub fn update(&mut self, new_input: InputController) {
-        self.mouse_state.left.was_down = self.mouse_state.left.is_down;
-        self.mouse_state.right.was_down = self.mouse_state.right.is_down;
-        self.mouse_state.middle.was_down = self.mouse_state.middle.is_down;
+        self.mouse_state.left.update(new_input.mouse_state.left.is_down);
+        self.mouse_state.right.update(new_input.mouse_state.right.is_down);
+        self.mouse_state.middle.update(new_input.mouse_state.middle.is_down);
         self.mouse_state.prev_pos = self.mouse_state.pos;
-        self.w.was_down = self.w.is_down;
-        self.a.was_down = self.a.is_down;
-        self.s.was_down = self.s.is_down;
-        self.d.was_down = self.d.is_down;
-        self.up.was_down = self.up.is_down;
-        self.left.was_down = self.left.is_down;
-        self.down.was_down = self.down.is_down;
-        self.right.was_down = self.right.is_down;
-        self.esc.was_down = self.esc.is_down;
-
-        self.mouse_state.left.is_down = new_input.mouse_state.left.is_down;
-        self.mouse_state.right.is_down = new_input.mouse_state.right.is_down;
-        self.mouse_state.middle.is_down = new_input.mouse_state.middle.is_down;
         self.mouse_state.pos = new_input.mouse_state.pos;
-        self.w.is_down = new_input.w.is_down;
-        self.a.is_down = new_input.a.is_down;
-        self.s.is_down = new_input.s.is_down;
-        self.d.is_down = new_input.d.is_down;
-        self.up.is_down = new_input.up.is_down;
-        self.left.is_down = new_input.left.is_down;
-        self.down.is_down = new_input.down.is_down;
-        self.right.is_down = new_input.right.is_down;
-        self.esc.is_down = new_input.esc.is_down;
+        self.mouse_state.delta = new_input.mouse_state.delta;
+        self.mouse_state.wheel_delta = new_input.mouse_state.wheel_delta;
+
+        self.keyboard.update(&new_input.keyboard);
+        self.modifiers = Modifiers::from_keyboard(&self.keyboard);
+
+        let mut i = 0;
+        while i < MAX_GAMEPADS {
+            self.gamepads[i].update(&new_input.gamepads[i]);
+            i += 1;
+        }
+
+        self.events = new_input.events;
+    }
+
+    pub fn pressed(&self, key: Key) -> bool {
+        self.keyboard.get(key).pressed()
+    }
+
+    pub fn released(&self, key: Key) -> bool {
+        self.keyboard.get(key).released()
+    }
+
+    pub fn held(&self, key: Key) -> bool {
+        self.keyboard.get(key).held()
+    }
+
+    // Characters typed this frame, in order
+    pub fn text(&self) -> impl Iterator<Item = char> + '_ {
+        self.events.iter().filter_map(|event| match event.event {
+            InputEvent::Text(character) => Some(character),
+            _ => None
+        })
     }
 }
 
+// How a pixel is laid out in OffscreenBuffer memory
+#[derive(Clone, Copy, PartialEq, Debug)]
+#[repr(u32)]
+pub enum PixelFormat {
+    // One u32 per pixel written as 0xAARRGGBB, so B, G, R, A in memory on little endian
+    Bgra8
+}
+
+// Plain description of the pixels the game draws into.
+// The platform layer owns the memory and whatever OS headers it needs to display it.
+#[repr(C)]
 pub struct OffscreenBuffer {
-    pub info: BITMAPINFO,
     pub memory: *mut c_void,
     pub width: u32,
     pub height: u32,
+    pub pitch: u32,
     pub bytes_per_pixel: u32,
-    pub pitch: u32
+    pub pixel_format: PixelFormat
+}
+
+// Interleaved signed 16 bit samples the game fills in for the platform to play
+#[repr(C)]
+pub struct SoundOutputBuffer {
+    pub samples_per_second: u32,
+    pub channel_count: u32,
+    // Per channel, the buffer holds sample_count * channel_count samples
+    pub sample_count: u32,
+    pub samples: *mut i16
+}
+
+// Memory the platform hands to the game. Both blocks start out zeroed and the game
+// decides what goes in them, so the host never needs to know what GameState looks like.
+#[repr(C)]
+pub struct GameMemory {
+    pub is_initialized: bool,
+    pub permanent_storage_size: u64,
+    // Kept across frames and hot reloads, GameState lives here
+    pub permanent_storage: *mut c_void,
+    pub transient_storage_size: u64,
+    // Scratch space the game can throw away at any time
+    pub transient_storage: *mut c_void,
+    pub services: PlatformServices
 }
 
+#[repr(C)]
+#[derive(Clone, Copy)]
 pub struct WindowDimensions {
     pub width: u32,
     pub height: u32
 }
 
+// Laid out in the order written so it can grow across reloads: a reload keeps every field in front
+// of the first one that changed and starts the rest over. New fields go at the end, and what's being
+// edited goes first so it's the last thing to get lost.
+#[repr(C)]
 #[derive(Default)]
 pub struct GameState {
-    pub delta_time: f32,
-    pub camera: Camera,
-    pub last_perf_print: u128,
     pub curves: [Option<BezierCurve>; 10],
+    // What each curve is filled with when closed with a straight line back to its start, 0 for not filled
+    pub curve_fill_colors: [u32; 10],
+    pub camera: Camera,
+    // The world height the wheel asked for, camera.height eases towards it
+    pub target_camera_height: f32,
+    pub undo_history: UndoHistory,
     pub selected_curve_index: Option<u32>,
-    pub selected_control_point: u32
+    pub selected_control_point: u32,
+    // Held zoom inputs zoom around the middle of the screen instead of the cursor
+    pub zoom_around_center: bool,
+    // The camera as it was before the last update, rendering blends from it to camera
+    pub previous_camera: Camera,
+    pub delta_time: f32,
+    pub last_perf_print: u128,
+    // PlatformServices::now at the last render, for the frame time
+    pub last_render_time: f64,
+    pub input_mapping: InputMapping,
+    // Scratch memory that is reset at the start of every update and render
+    pub transient_arena: MemoryArena
+}
+
+const UNDO_HISTORY_SIZE: usize = 32;
+
+// The curves as they were before each edit, the oldest ones get dropped once it's full
+#[derive(Clone, Copy)]
+pub struct UndoHistory {
+    count: u32,
+    next: u32,
+    entries: [[Option<BezierCurve>; 10]; UNDO_HISTORY_SIZE]
+}
+
+impl Default for UndoHistory {
+    fn default() -> Self {
+        UndoHistory {
+            count: 0,
+            next: 0,
+            entries: [[None; 10]; UNDO_HISTORY_SIZE]
+        }
+    }
+}
+
+impl UndoHistory {
+    pub fn push(&mut self, curves: &[Option<BezierCurve>; 10]) {
+        self.entries[self.next as usize] = *curves;
+        self.next = (self.next + 1) % UNDO_HISTORY_SIZE as u32;
+        self.count = min(self.count + 1, UNDO_HISTORY_SIZE as u32);
+    }
+
+    pub fn pop(&mut self) -> Option<[Option<BezierCurve>; 10]> {
+        if self.count == 0 {
+            return None;
+        }
+
+        self.next = (self.next + UNDO_HISTORY_SIZE as u32 - 1) % UNDO_HISTORY_SIZE as u32;
+        self.count -= 1;
+        Some(self.entries[self.next as usize])
+    }
 }
 
 #[derive(Default, Clone, Copy)]
@@ -112,6 +271,22 @@ impl Camera {
         }
     }
 
+    // Changes the visible world height while keeping the world point at screen_pos where it is.
+    // y_scale and width follow along so the window stays filled.
+    fn zoom_to(&mut self, height: f32, screen_pos: Vector2i32) {
+        let anchor_before = screen_space_to_world_space(*self, screen_pos);
+
+        let buffer_height = self.y_scale * self.height;
+        let buffer_width = self.y_scale * self.width;
+        self.height = height;
+        self.y_scale = buffer_height / height;
+        self.width = buffer_width / self.y_scale;
+
+        let anchor_after = screen_space_to_world_space(*self, screen_pos);
+        self.x += anchor_before.x - anchor_after.x;
+        self.y += anchor_before.y - anchor_after.y;
+    }
+
     fn get_bounding_box(self) -> Rectangle {
         Rectangle {
             x: self.x - self.width / 2.0,
@@ -150,7 +325,7 @@ impl std::ops::Sub for Vector2 {
 
     fn sub(self, other: Vector2) -> Self {
         Vector2 {
-            x: self.y - other.x,
+            x: self.x - other.x,
             y: self.y - other.y
         }
     }
@@ -167,7 +342,7 @@ impl std::ops::Mul<f32> for Vector2 {
     }
 }
 
-#[derive(Clone, Copy, Default, PartialEq)]
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
 pub struct Vector2u32 {
     pub x: u32,
     pub y: u32
@@ -204,10 +379,10 @@ impl std::ops::Sub<Vector2u32> for Vector2u32 {
     }
 }
 
-#[derive(Clone, Copy)]
-struct Vector2i32 {
-    x: i32,
-    y: i32
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct Vector2i32 {
+    pub x: i32,
+    pub y: i32
 }
 
 #[derive(Default, Clone, Copy, Debug)]
@@ -249,7 +424,7 @@ impl BezierCurve {
     // A faster method might be to evaluate the whole curve and look for min and max values
     // Could be worth benchmarking to see what's faster
     // This is obviously more accurate tho
-    fn get_bounding_box(&self) -> Rectangle {
+    fn get_bounding_box(&self, arena: &mut MemoryArena) -> Rectangle {
         let tx = {
             let a = -3.0 * self.p0.x + 9.0 * self.p1.x -9.0 * self.p2.x + 3.0 * self.p3.x;
             let b = 6.0 * self.p0.x -12.0 * self.p1.x + 6.0 * self.p2.x;
@@ -286,29 +461,33 @@ impl BezierCurve {
             }
         };
 
-        let mut points: Vec<Vector2> = Vec::new();
+        // The two end points plus at most two extremes per axis
+        let points = arena.push_slice(6, Vector2::zero());
+        let mut point_count = 0;
 
-        points.push(self.evaluate(0.0));
-        points.push(self.evaluate(1.0));
+        points[point_count] = self.evaluate(0.0);
+        point_count += 1;
+        points[point_count] = self.evaluate(1.0);
+        point_count += 1;
 
         if tx.0 < 1.0 && tx.0 > 0.0 {
-            let point = self.evaluate(tx.0);
-            points.push(point);
+            points[point_count] = self.evaluate(tx.0);
+            point_count += 1;
         }
 
         if ty.0 < 1.0 && ty.0 > 0.0 {
-            let point = self.evaluate(ty.0);
-            points.push(point);
+            points[point_count] = self.evaluate(ty.0);
+            point_count += 1;
         }
 
         if tx.1 < 1.0 && tx.1 > 0.0 {
-            let point = self.evaluate(tx.1);
-            points.push(point);
+            points[point_count] = self.evaluate(tx.1);
+            point_count += 1;
         }
 
         if ty.1 < 1.0 && ty.1 > 0.0 {
-            let point = self.evaluate(ty.1);
-            points.push(point);
+            points[point_count] = self.evaluate(ty.1);
+            point_count += 1;
         }
 
         let mut min_x = f32::MAX;
@@ -316,7 +495,7 @@ impl BezierCurve {
         let mut max_x = f32::MIN;
         let mut max_y = f32::MIN;
 
-        for point in points {
+        for point in &points[..point_count] {
             if point.x < min_x {
                 min_x = point.x;
             } else if point.x > max_x {
@@ -344,71 +523,322 @@ impl BezierCurve {
         self.p2 * (-3.0 * t * t * t + 3.0 * t * t) +
         self.p3 * (t * t * t)
     }
+
+    // How many straight segments it takes for none of them to be further than tolerance from the
+    // curve, tolerance being in the same units as the points. Wang's formula, it only needs how
+    // sharply the control polygon bends, so it's cheap enough to work out for every curve every frame.
+    fn segment_count(&self, tolerance: f32) -> usize {
+        let bend = |a: Vector2, b: Vector2, c: Vector2| {
+            let x = a.x - 2.0 * b.x + c.x;
+            let y = a.y - 2.0 * b.y + c.y;
+            (x * x + y * y).sqrt()
+        };
+        let max_bend = bend(self.p0, self.p1, self.p2).max(bend(self.p1, self.p2, self.p3));
+
+        // 3 * 2 / 8 for a cubic, NaN from a bad tolerance comes out as 0 and gets clamped
+        let count = (0.75 * max_bend / tolerance).sqrt().ceil() as usize;
+        count.clamp(1, MAX_CURVE_SEGMENTS)
+    }
+
+    // Points along the curve from p0 to p3, close enough together that the straight segments
+    // between them stay within tolerance of it. Returns how many of points it filled in.
+    fn flatten(&self, tolerance: f32, points: &mut [Vector2; MAX_CURVE_SEGMENTS + 1]) -> usize {
+        let segment_count = self.segment_count(tolerance);
+
+        // From the step number each time rather than adding up a step in t, so the last point is exactly p3
+        let mut i = 0;
+        while i <= segment_count {
+            points[i] = self.evaluate(i as f32 / segment_count as f32);
+            i += 1;
+        }
+
+        segment_count + 1
+    }
 }
 
-static CAMERA_SPEED: f32 = 0.005;
-static CAMERA_SPEED_DIAG: f32 = 0.0035;
+// How far in pixels a curve drawn as straight segments can stray from the real one
+const CURVE_TOLERANCE: f32 = 0.2;
+// Only reached by curves far bigger than the screen, where nobody sees the difference
+const MAX_CURVE_SEGMENTS: usize = 256;
+
+// Speeds are in camera heights per millisecond so they feel the same at any zoom
+static CAMERA_SPEED: f32 = 0.005 / 9.0;
+static POINT_MOVE_SPEED: f32 = 0.0004;
+
+// Limits on the visible world height, smaller is zoomed in
+static CAMERA_MIN_HEIGHT: f32 = 0.5;
+static CAMERA_MAX_HEIGHT: f32 = 200.0;
+// How much one notch of the wheel (WHEEL_DELTA, 120) zooms in
+static ZOOM_PER_WHEEL_NOTCH: f32 = 1.15;
+// Per millisecond while a zoom key or trigger is all the way down, in log space like the wheel
+static ZOOM_SPEED: f32 = 0.002;
+// Milliseconds for the camera to get about two thirds of the way to the target height
+static ZOOM_SMOOTHING_TIME: f32 = 60.0;
+
+// In pixels so curves stay as thick at any zoom
+static CURVE_STYLE: StrokeStyle = StrokeStyle { width: StrokeWidth::Screen(2.0), join: LineJoin::Round, cap: LineCap::Round };
+static SELECTED_CURVE_STYLE: StrokeStyle = StrokeStyle { width: StrokeWidth::Screen(4.0), join: LineJoin::Round, cap: LineCap::Round };
+
+// What CycleFill goes through, starting over at not filled
+static FILL_COLORS: [u32; 4] = [0, 0x803399FF, 0x80FF6633, 0x8066DD66];
+
+// Bump when what GameState's fields mean changes and teach migrate_game_state how to get there
+// from the old version. Changes to where they are get caught by game_state_layout on their own.
+const GAME_STATE_VERSION: u32 = 4;
+
+// Sits in front of GameState at the start of permanent storage
+#[repr(C)]
+struct GameStateHeader {
+    version: u32,
+    field_count: u32,
+    // game_state_layout of the library that wrote the state
+    field_hashes: [u64; MAX_GAME_STATE_FIELDS]
+}
+
+fn initial_game_state() -> GameState {
+    let mut game_state = GameState {
+        camera: Camera::new(0.0, 0.0, 16.0, 9.0),
+        target_camera_height: 9.0,
+        ..Default::default()
+    };
+
+    game_state.curves[0] = Some(BezierCurve::new(
+        Vector2 { x: 0.0, y: 0.5 },
+        Vector2 { x: 1.0, y: 0.0 },
+        Vector2 { x: 1.0, y: 1.6 },
+        Vector2 { x: 0.0, y: 2.0 }
+    ));
+
+    game_state
+}
+
+// Finds GameState in permanent storage, setting it up on the first frame
+// and bringing it up to date after a reload changed its shape
+unsafe fn get_game_state(memory: &mut GameMemory) -> &mut GameState {
+    let state_offset = size_of::<GameStateHeader>().next_multiple_of(align_of::<GameState>());
+    assert!((state_offset + size_of::<GameState>()) as u64 <= memory.permanent_storage_size,
+        "Permanent storage is too small for GameState");
+
+    let header = memory.permanent_storage as *mut GameStateHeader;
+    let game_state = (memory.permanent_storage as *mut u8).add(state_offset) as *mut GameState;
+
+    let layout = game_state_layout();
+    let old_hashes = (*header).field_hashes;
+
+    if !memory.is_initialized {
+        ptr::write(game_state, initial_game_state());
+        memory.is_initialized = true;
+    } else if (*header).version != GAME_STATE_VERSION
+        || (*header).field_count as usize != layout.field_count
+        || old_hashes[..layout.field_count] != layout.hashes[..layout.field_count] {
+        migrate_game_state(&memory.services, &*header, &layout, game_state);
+    }
+
+    (*header).version = GAME_STATE_VERSION;
+    (*header).field_count = layout.field_count as u32;
+    (*header).field_hashes = layout.hashes;
+
+    let game_state = &mut *game_state;
+
+    // The state may have been restored from a recording made by another process, where transient
+    // storage was somewhere else, so point the arena at wherever it is now.
+    // A zeroed arena from a fresh state gets set up the same way.
+    game_state.transient_arena.rebase(memory.transient_storage, memory.transient_storage_size);
+
+    game_state
+}
+
+// Keeps the fields laid out the same as in the old state and starts the rest over.
+// Works on bytes, the fields that changed could hold anything and mustn't be read as what they are now.
+unsafe fn migrate_game_state(services: &PlatformServices, old_header: &GameStateHeader, layout: &GameStateLayout, game_state: *mut GameState) {
+    let mut kept_count = 0;
+    if old_header.version == GAME_STATE_VERSION {
+        let old_field_count = min(old_header.field_count as usize, MAX_GAME_STATE_FIELDS);
+        while kept_count < min(old_field_count, layout.field_count)
+            && old_header.field_hashes[kept_count] == layout.hashes[kept_count] {
+            kept_count += 1;
+        }
+    }
+    let kept_size = if kept_count == 0 { 0 } else { layout.ends[kept_count - 1] };
+
+    let mut new_state = initial_game_state();
+    ptr::copy_nonoverlapping(game_state as *const u8, &mut new_state as *mut GameState as *mut u8, kept_size);
+    ptr::write(game_state, new_state);
+
+    if kept_count == layout.field_count {
+        log!(services, LogLevel::Info, "GameState lost {} fields from the end, kept the other {}",
+            old_header.field_count as usize - kept_count, kept_count);
+    } else {
+        log!(services, LogLevel::Warning, "GameState changed from version {} ({} fields) to {} ({} fields), kept the first {} and reset the rest",
+            old_header.version, old_header.field_count, GAME_STATE_VERSION, layout.field_count, kept_count);
+    }
+}
+
+// One fixed step of the simulation, input_controller.delta_time is always the same length
+unsafe extern "C" fn game_update(memory: &mut GameMemory, input_controller: &mut InputController, screen: &WindowDimensions) {
+    let services = memory.services;
+    update(get_game_state(memory), &services, input_controller, screen);
+}
+
+unsafe extern "C" fn game_render(memory: &mut GameMemory, buffer: &mut OffscreenBuffer, blend_factor: f32) {
+    let services = memory.services;
+    render(get_game_state(memory), &services, &mut Canvas::from_buffer(buffer), blend_factor);
+}
+
+fn update(game_state: &mut GameState, services: &PlatformServices, input_controller: &InputController, screen: &WindowDimensions) {
+
+    game_state.delta_time = input_controller.delta_time;
+    game_state.previous_camera = game_state.camera;
+
+    // Keep the world's height fixed and fit the width to the window
+    fit_camera_to_screen(&mut game_state.camera, screen.width, screen.height);
+
+    game_state.transient_arena.reset();
+
+    game_state.input_mapping.reload_if_changed(services, &mut game_state.transient_arena);
+    game_state.input_mapping.actions.update(&game_state.input_mapping.bindings, input_controller);
 
-#[no_mangle]
-pub unsafe fn game_update_and_render(game_state: &mut GameState, input_controller: &mut InputController, buffer: &mut OffscreenBuffer) {
     handle_inputs(*input_controller, game_state);
 
-    clear_buffer(buffer);
-    draw_unit_grid(buffer, game_state.camera);
-    draw_circle(buffer, game_state.camera, Vector2::zero(), 0.05, 0xFFFF0000);
-    draw_bounding_boxes(buffer, game_state);
-    draw_bezier_curves(buffer, game_state);
-    draw_control_points(buffer, game_state.camera, game_state);
+    // The host only hides and locks the cursor when asked, and asking every update means
+    // it catches up on its own after a reload or when the host starts over
+    services.set_relative_mouse(game_state.input_mapping.actions.get(Action::LockedPan).held());
+}
+
+// Draws the state blend_factor of the way from the update before last to the last one,
+// so movement looks smooth when frames and updates don't line up
+fn render(game_state: &mut GameState, services: &PlatformServices, canvas: &mut Canvas, blend_factor: f32) {
+    game_state.transient_arena.reset();
+
+    let camera = interpolate_camera(game_state.previous_camera, game_state.camera, blend_factor, canvas);
+
+    canvas.clear(0);
+    draw_unit_grid(canvas, camera);
+    draw_circle(canvas, camera, Vector2::zero(), 0.05, 0xFFFF0000);
+    draw_bounding_boxes(canvas, camera, &game_state.curves, &mut game_state.transient_arena);
+
+    // Shared by everything that fills or strokes, it's left empty after every fill
+    let cells = game_state.transient_arena.push_slice(Rasterizer::cell_count(canvas.width(), canvas.height()), 0.0);
+    let mut rasterizer = Rasterizer::new(cells, canvas.width(), canvas.height());
+    draw_curve_fills(canvas, camera, &mut rasterizer, &game_state.curves, &game_state.curve_fill_colors);
+    draw_bezier_curves(canvas, camera, &mut rasterizer, &game_state.curves, game_state.selected_curve_index);
+    draw_control_points(canvas, camera, game_state);
+
+    let time_now = services.now();
+    let frame_time = time_now - game_state.last_render_time;
+    game_state.last_render_time = time_now;
+
+    // abs_diff since the clock starts over when the host restarts but GameState might not
+    if (time_now as u128).abs_diff(game_state.last_perf_print) >= 1000 {
+        log!(services, LogLevel::Debug, "Frame time: {}", frame_time);
+        log!(services, LogLevel::Debug, "FPS: {}", 1000.0 / frame_time);
+        log!(services, LogLevel::Debug, "Transient arena: {} / {} bytes (high water {})",
+            game_state.transient_arena.used(), game_state.transient_arena.size(), game_state.transient_arena.high_water_mark());
+        game_state.last_perf_print = time_now as u128;
+    }
+}
 
-    let start = SystemTime::now();
-    let time_now = start.duration_since(UNIX_EPOCH).expect("Time went backwards");
-    if time_now.as_millis() - game_state.last_perf_print >= 1000 {
-        println!("Frame time: {}", game_state.delta_time);
-        println!("FPS: {}", 1000.0 / game_state.delta_time);
-        game_state.last_perf_print = time_now.as_millis();
+fn fit_camera_to_screen(camera: &mut Camera, width: u32, height: u32) {
+    camera.y_scale = height as f32 / camera.height;
+    camera.width = width as f32 / camera.y_scale;
+}
+
+fn interpolate_camera(previous: Camera, current: Camera, blend_factor: f32, canvas: &Canvas) -> Camera {
+    let mut camera = current;
+
+    // Zero from before there was interpolation, or before the first update
+    if previous.height != 0.0 {
+        let lerp = |a: f32, b: f32| a + (b - a) * blend_factor;
+        camera.x = lerp(previous.x, current.x);
+        camera.y = lerp(previous.y, current.y);
+        // In log space like the zoom itself. Skipped when it didn't change, so a still camera
+        // renders exactly the same everywhere and doesn't depend on how ln and exp round.
+        if previous.height != current.height {
+            camera.height = lerp(previous.height.ln(), current.height.ln()).exp();
+        }
     }
+
+    fit_camera_to_screen(&mut camera, canvas.width(), canvas.height());
+    camera
 }
 
 fn handle_inputs(input: InputController, game_state: &mut GameState) {
-    // Keyboard camera movement
-    let move_up = input.w.is_down || input.up.is_down;
-    let move_down = input.s.is_down || input.down.is_down;
-    let move_left = input.a.is_down || input.left.is_down;
-    let move_right = input.d.is_down || input.right.is_down;
-
-    if move_up && move_left {
-        (*game_state).camera.x -= CAMERA_SPEED_DIAG * game_state.delta_time;
-        (*game_state).camera.y -= CAMERA_SPEED_DIAG * game_state.delta_time;
-    } else if move_up && move_right {
-        (*game_state).camera.x += CAMERA_SPEED_DIAG * game_state.delta_time;
-        (*game_state).camera.y -= CAMERA_SPEED_DIAG * game_state.delta_time;
-    } else if move_down && move_left {
-        (*game_state).camera.x -= CAMERA_SPEED_DIAG * game_state.delta_time;
-        (*game_state).camera.y += CAMERA_SPEED_DIAG * game_state.delta_time;
-    } else if move_down && move_right {
-        (*game_state).camera.x += CAMERA_SPEED_DIAG * game_state.delta_time;
-        (*game_state).camera.y += CAMERA_SPEED_DIAG * game_state.delta_time;
-    } else if move_up {
-        (*game_state).camera.y -= CAMERA_SPEED * game_state.delta_time;
-    } else if move_down {
-        (*game_state).camera.y += CAMERA_SPEED * game_state.delta_time;
-    } else if move_left {
-        (*game_state).camera.x -= CAMERA_SPEED * game_state.delta_time;
-    } else if move_right {
-        (*game_state).camera.x += CAMERA_SPEED * game_state.delta_time;
-    }
-
-    // Mouse left click actions
-    let left_down = input.mouse_state.left.is_down;
-    let left_released = !left_down && input.mouse_state.left.was_down;
-    let left_pressed = left_down && !input.mouse_state.left.was_down;
-
-    if left_released {
+    let actions = game_state.input_mapping.actions;
+
+    // Analog inputs pan slower, and diagonals aren't any faster than straight lines
+    let mut pan = Vector2 {
+        x: actions.get(Action::PanRight).value - actions.get(Action::PanLeft).value,
+        y: actions.get(Action::PanDown).value - actions.get(Action::PanUp).value
+    };
+    let pan_length = (pan.x * pan.x + pan.y * pan.y).sqrt();
+    if pan_length > 1.0 {
+        pan = pan * (1.0 / pan_length);
+    }
+
+    let pan_speed = CAMERA_SPEED * game_state.camera.height * game_state.delta_time;
+    game_state.camera.x += pan.x * pan_speed;
+    game_state.camera.y += pan.y * pan_speed;
+
+    handle_zoom(&input, &actions, game_state);
+
+    if actions.get(Action::Undo).pressed() {
+        if let Some(curves) = game_state.undo_history.pop() {
+            game_state.curves = curves;
+        }
+    }
+
+    if actions.get(Action::CycleFill).pressed() {
+        if let Some(index) = game_state.selected_curve_index {
+            let fill_color = &mut game_state.curve_fill_colors[index as usize];
+            let current = FILL_COLORS.iter().position(|color| color == fill_color).unwrap_or(0);
+            *fill_color = FILL_COLORS[(current + 1) % FILL_COLORS.len()];
+        }
+    }
+
+    if actions.get(Action::NextPoint).pressed() {
+        select_next_control_point(game_state, 1);
+    }
+    if actions.get(Action::PreviousPoint).pressed() {
+        select_next_control_point(game_state, -1);
+    }
+
+    // Moving the selected control point without the mouse
+    let move_actions = [Action::MovePointUp, Action::MovePointDown, Action::MovePointLeft, Action::MovePointRight];
+    let point_move = Vector2 {
+        x: actions.get(Action::MovePointRight).value - actions.get(Action::MovePointLeft).value,
+        y: actions.get(Action::MovePointDown).value - actions.get(Action::MovePointUp).value
+    };
+
+    if let Some(index) = game_state.selected_curve_index {
+        // One undo step per push of the stick
+        let move_started = move_actions.iter().any(|action| actions.get(*action).held())
+            && !move_actions.iter().any(|action| actions.get(*action).button.was_down);
+        if move_started {
+            game_state.undo_history.push(&game_state.curves);
+        }
+
+        let offset = point_move * (POINT_MOVE_SPEED * game_state.camera.height * game_state.delta_time);
+        if let Some(curve) = &mut game_state.curves[index as usize] {
+            if game_state.selected_control_point == 0 {
+                curve.p1 = curve.p1 + offset;
+            } else {
+                curve.p2 = curve.p2 + offset;
+            }
+        }
+    }
+
+    // Picking up and dragging control points, or the camera when there's nothing under the cursor
+    let select = actions.get(Action::SelectPoint);
+
+    if select.released() {
         game_state.selected_curve_index = None;
     }
 
     let cursor_pos_world = screen_space_to_world_space(game_state.camera, input.mouse_state.pos);
-    if left_pressed {
+    if select.pressed() {
+        // Whatever NextPoint picked doesn't count, a click that misses every point drags the camera
+        game_state.selected_curve_index = None;
+
         let mut i = 0;
         while i < game_state.curves.len() as u32 {
             match game_state.curves[i as usize] {
@@ -428,9 +858,18 @@ fn handle_inputs(input: InputController, game_state: &mut GameState) {
 
             i += 1;
         }
+
+        // Went down and back up within the frame, so it's a release as well and released() never sees it
+        if !select.held() {
+            game_state.selected_curve_index = None;
+        }
+
+        if game_state.selected_curve_index.is_some() {
+            game_state.undo_history.push(&game_state.curves);
+        }
     }
 
-    if left_down {
+    if select.held() {
         match game_state.selected_curve_index {
             Some(index) => {
                 // Moving control point with mouse
@@ -451,49 +890,112 @@ fn handle_inputs(input: InputController, game_state: &mut GameState) {
             },
             None => {
                 // Moving camera with mouse
-                let mouse_delta = Vector2i32 {
-                    x: input.mouse_state.prev_pos.x as i32 - input.mouse_state.pos.x as i32,
-                    y: input.mouse_state.prev_pos.y as i32 - input.mouse_state.pos.y as i32
-                };
-
-                (*game_state).camera.x += mouse_delta.x as f32 / game_state.camera.y_scale;
-                (*game_state).camera.y += mouse_delta.y as f32 / game_state.camera.y_scale;
+                drag_camera(&mut game_state.camera, input.mouse_state.delta);
             }
         }
     }
 
-    // Reset camera
-    let right_clicked = input.mouse_state.right.is_down && !input.mouse_state.right.was_down;
-    if right_clicked {
+    if actions.get(Action::LockedPan).held() {
+        drag_camera(&mut game_state.camera, input.mouse_state.delta);
+    }
+
+    if actions.get(Action::ResetCamera).pressed() {
         (*game_state).camera.x = 0.0;
         (*game_state).camera.y = 0.0;
     }
 }
 
-fn world_space_to_screen_space(camera: Camera, pos: Vector2) -> Vector2u32 {
-    let x = ((pos.x - camera.x + camera.width / 2.0) * camera.y_scale) as u32;
-    let y = ((pos.y - camera.y + camera.height / 2.0) * camera.y_scale) as u32;
-    Vector2u32 { x, y }
+// Moves the camera so the world follows the mouse
+fn drag_camera(camera: &mut Camera, mouse_delta: Vector2i32) {
+    camera.x -= mouse_delta.x as f32 / camera.y_scale;
+    camera.y -= mouse_delta.y as f32 / camera.y_scale;
+}
+
+// Steps (the wheel) zoom a notch at a time around the cursor,
+// held inputs (keys, triggers) zoom steadily around the middle of the screen
+fn handle_zoom(input: &InputController, actions: &ActionStates, game_state: &mut GameState) {
+    let camera = &mut game_state.camera;
+
+    let steps = actions.get(Action::ZoomIn).steps - actions.get(Action::ZoomOut).steps;
+    if steps != 0.0 {
+        game_state.target_camera_height /= ZOOM_PER_WHEEL_NOTCH.powf(steps);
+        game_state.zoom_around_center = false;
+    }
+
+    let rate = actions.get(Action::ZoomIn).value - actions.get(Action::ZoomOut).value;
+    if rate != 0.0 {
+        game_state.target_camera_height *= (-rate * ZOOM_SPEED * game_state.delta_time).exp();
+        game_state.zoom_around_center = true;
+    }
+
+    game_state.target_camera_height = game_state.target_camera_height.clamp(CAMERA_MIN_HEIGHT, CAMERA_MAX_HEIGHT);
+
+    let target = game_state.target_camera_height;
+    if camera.height == target {
+        return;
+    }
+
+    // Eased in log space so zooming in and out feel the same at any zoom level
+    let t = 1.0 - (-game_state.delta_time / ZOOM_SMOOTHING_TIME).exp();
+    let mut height = (camera.height.ln() + (target.ln() - camera.height.ln()) * t).exp();
+    if (height - target).abs() < target * 0.001 {
+        height = target;
+    }
+
+    let anchor = if game_state.zoom_around_center {
+        Vector2i32 {
+            x: (camera.width * camera.y_scale / 2.0) as i32,
+            y: (camera.height * camera.y_scale / 2.0) as i32
+        }
+    } else {
+        input.mouse_state.pos
+    };
+
+    camera.zoom_to(height, anchor);
+}
+
+// Steps through the control points of every curve in order, wrapping around at either end
+fn select_next_control_point(game_state: &mut GameState, direction: i32) {
+    let point_count = game_state.curves.len() as i32 * 2;
+
+    let mut point = match game_state.selected_curve_index {
+        Some(index) => index as i32 * 2 + game_state.selected_control_point as i32,
+        // So the first step lands on the first or last point
+        None => if direction > 0 { -1 } else { point_count }
+    };
+
+    let mut steps = 0;
+    while steps < point_count {
+        point = (point + direction).rem_euclid(point_count);
+        if game_state.curves[(point / 2) as usize].is_some() {
+            game_state.selected_curve_index = Some((point / 2) as u32);
+            game_state.selected_control_point = (point % 2) as u32;
+            return;
+        }
+        steps += 1;
+    }
+}
+
+fn world_space_to_screen_space(camera: Camera, pos: Vector2) -> Vector2i32 {
+    let screen_pos = world_space_to_screen_space_f32(camera, pos);
+    Vector2i32 { x: screen_pos.x as i32, y: screen_pos.y as i32 }
 }
 
-fn world_space_to_screen_space_i32(camera: Camera, pos: Vector2) -> Vector2i32 {
-    let x = ((pos.x - camera.x + camera.width / 2.0) * camera.y_scale) as i32;
-    let y = ((pos.y - camera.y + camera.height / 2.0) * camera.y_scale) as i32;
-    Vector2i32 { x, y }
+// Without rounding to whole pixels, for drawing that can make use of where inside a pixel things are
+fn world_space_to_screen_space_f32(camera: Camera, pos: Vector2) -> Vector2 {
+    let x = (pos.x - camera.x + camera.width / 2.0) * camera.y_scale;
+    let y = (pos.y - camera.y + camera.height / 2.0) * camera.y_scale;
+    Vector2 { x, y }
 }
 
-fn screen_space_to_world_space(camera: Camera, pos: Vector2u32) -> Vector2 {
+fn screen_space_to_world_space(camera: Camera, pos: Vector2i32) -> Vector2 {
     let x = pos.x as f32 / camera.y_scale + camera.x - camera.width / 2.0;
     let y = pos.y as f32 / camera.y_scale + camera.y - camera.height / 2.0;
     Vector2 { x, y }
 }
 
-unsafe fn clear_buffer(buffer: &mut OffscreenBuffer) {
-    ptr::write_bytes((*buffer).memory, 0u8, (buffer.height * buffer.width * buffer.bytes_per_pixel) as usize);
-}
-
 // TODO: ????
-unsafe fn draw_unit_grid(buffer: &mut OffscreenBuffer, camera: Camera) {
+fn draw_unit_grid(canvas: &mut Canvas, camera: Camera) {
     // Horizontal lines
     let camera_height_fpart = camera.height / 2.0 - ((camera.height / 2.0) as i32) as f32;
     let camera_y_fpart = if camera.y >= 0.0 {
@@ -505,11 +1007,11 @@ unsafe fn draw_unit_grid(buffer: &mut OffscreenBuffer, camera: Camera) {
 
     let mut line_y: u32 = 0;
     while line_y < camera.height as u32 {
-        let y = (((line_y as f32 - y_offset) * camera.y_scale) as i32).rem_euclid(buffer.height as i32) as u32;
+        let y = (((line_y as f32 - y_offset) * camera.y_scale) as i32).rem_euclid(canvas.height() as i32);
 
-        let mut x: u32 = 0;
-        while x < buffer.width as u32 {
-            draw_pixel_to_buffer(buffer, x, y, 0xFF444444);
+        let mut x = 0;
+        while x < canvas.width() as i32 {
+            canvas.blend_pixel(x, y, 0xFF444444);
             x += 1;
         }
         line_y += 1;
@@ -523,101 +1025,33 @@ unsafe fn draw_unit_grid(buffer: &mut OffscreenBuffer, camera: Camera) {
         1.0 + (camera.x + (camera.x.abs() as i32) as f32)
     };
     let x_offset = camera_x_fpart - camera_width_fpart;
-    println!("offset: {}", x_offset);
 
     let mut line_x: u32 = 0;
     while line_x < camera.width as u32 {
-        let x = (((line_x as f32 - x_offset) * camera.y_scale) as i32).rem_euclid(buffer.width as i32) as u32;
+        let x = (((line_x as f32 - x_offset) * camera.y_scale) as i32).rem_euclid(canvas.width() as i32);
 
-        let mut y: u32 = 0;
-        while y < buffer.height as u32 {
-            draw_pixel_to_buffer(buffer, x, y, 0xFF444444);
+        let mut y = 0;
+        while y < canvas.height() as i32 {
+            canvas.blend_pixel(x, y, 0xFF444444);
             y += 1;
         }
         line_x += 1;
     }
 }
 
-// TODO: Make sure transparent lines work properly
-// TODO: Fix drawing out of bounds
-// Xiaolin Wu's line algorithm
-unsafe fn draw_line(buffer: &mut OffscreenBuffer, camera: Camera, a: Vector2, b: Vector2, color: u32) {
-    let a_screen = world_space_to_screen_space_i32(camera, a);
-    let b_screen = world_space_to_screen_space_i32(camera, b);
-
-    let mut x0 = a_screen.x;
-    let mut y0 = a_screen.y;
-    let mut x1 = b_screen.x;
-    let mut y1 = b_screen.y;
-
-    let steep = y1 - y0 > x1 - x0;
-
-    if steep {
-        let old_x0 = x0;
-        x0 = y0;
-        y0 = old_x0;
-
-        let old_x1 = x1;
-        x1 = y1;
-        y1 = old_x1;
-    }
-
-    if x0 > x1 {
-        let old_x0 = x0;
-        x0 = x1;
-        x1 = old_x0;
-
-        let old_y0 = y0;
-        y0 = y1;
-        y1 = old_y0;
-    }
-
-    let gradient = if x1 - x0 == 0 {
-        1.0
-    } else {
-        (y1 - y0) as f32 / (x1 - x0) as f32
-    };
-
-    let mut y_intersect = y0 as f32;
-
-    if steep {
-        let mut x = x0;
-        while x <= x1 {
-            let y_intersect_fpart = y_intersect as f32 - (y_intersect as u32) as f32;
-            let alpha = ((1.0 - y_intersect_fpart) * 255.0) as u32;
-            let color_with_alpha = (color & 0x00FFFFFF) | (alpha << 24);
-
-            draw_pixel_to_buffer(buffer, y_intersect as u32, max(x, 0) as u32, color);
-            draw_pixel_to_buffer(buffer, max(y_intersect as i32 - 1, 0) as u32, max(x, 0) as u32, color_with_alpha);
-
-            y_intersect += gradient;
-            x += 1;
-        }
-    } else {
-        let mut x = x0;
-        while x <= x1 {
-            let y_intersect_fpart = y_intersect as f32 - (y_intersect as u32) as f32;
-            let alpha = ((1.0 - y_intersect_fpart) * 255.0) as u32;
-            let color_with_alpha = (color & 0x00FFFFFF) | (alpha << 24);
-
-            draw_pixel_to_buffer(buffer, max(x, 0) as u32, y_intersect as u32, color);
-            draw_pixel_to_buffer(buffer, max(x, 0) as u32, max(y_intersect as i32 - 1, 0) as u32, color_with_alpha);
-
-            y_intersect += gradient;
-            x += 1;
-        }
-    }
+fn draw_line(canvas: &mut Canvas, camera: Camera, a: Vector2, b: Vector2, color: u32) {
+    canvas.draw_line(world_space_to_screen_space_f32(camera, a), world_space_to_screen_space_f32(camera, b), color);
 }
 
-unsafe fn draw_control_points(buffer: &mut OffscreenBuffer, camera: Camera, game_state: &GameState) {
+fn draw_control_points(canvas: &mut Canvas, camera: Camera, game_state: &GameState) {
     for curve in game_state.curves {
         match curve {
             Some(value) => {
-                draw_line(buffer, camera, value.p0, value.p1, 0xFF888888);
-                draw_line(buffer, camera, value.p2, value.p3, 0xFF888888);
+                draw_line(canvas, camera, value.p0, value.p1, 0xFF888888);
+                draw_line(canvas, camera, value.p2, value.p3, 0xFF888888);
 
-                draw_circle(buffer, camera, value.p1, 0.02, 0xFF00FF00);
-                draw_circle(buffer, camera, value.p2, 0.02, 0xFF00FF00);
+                draw_circle(canvas, camera, value.p1, 0.02, 0xFF00FF00);
+                draw_circle(canvas, camera, value.p2, 0.02, 0xFF00FF00);
             },
             None => {
                 continue;
@@ -626,19 +1060,19 @@ unsafe fn draw_control_points(buffer: &mut OffscreenBuffer, camera: Camera, game
     }
 }
 
-unsafe fn draw_bounding_boxes(buffer: &mut OffscreenBuffer, game_state: &GameState) {
-    let camera_bounding_box = game_state.camera.get_bounding_box();
+fn draw_bounding_boxes(canvas: &mut Canvas, camera: Camera, curves: &[Option<BezierCurve>], arena: &mut MemoryArena) {
+    let camera_bounding_box = camera.get_bounding_box();
 
-    for curve in game_state.curves {
+    for curve in curves {
         match curve {
             Some(value) => {
-                let bounding_box = value.get_bounding_box();
+                let bounding_box = value.get_bounding_box(arena);
 
                 if !bounding_box.intersects(camera_bounding_box) {
                     continue;
                 }
 
-                draw_rectangle(buffer, game_state.camera, bounding_box, 0x3300DDAA);
+                draw_rectangle(canvas, camera, bounding_box, 0x3300DDAA);
             }
             None => {
                 continue;
@@ -647,7 +1081,7 @@ unsafe fn draw_bounding_boxes(buffer: &mut OffscreenBuffer, game_state: &GameSta
     }
 }
 
-unsafe fn draw_rectangle(buffer: &mut OffscreenBuffer, camera: Camera, rectangle: Rectangle, color: u32) {
+fn draw_rectangle(canvas: &mut Canvas, camera: Camera, rectangle: Rectangle, color: u32) {
     let rect_top_left = Vector2 {
         x: rectangle.x,
         y: rectangle.y
@@ -662,50 +1096,15 @@ unsafe fn draw_rectangle(buffer: &mut OffscreenBuffer, camera: Camera, rectangle
     let rect_top_left_screen = world_space_to_screen_space(camera, rect_top_left);
     let rect_bottom_right_screen = world_space_to_screen_space(camera, rect_bottom_right);
 
-    let start_x = max(rect_top_left_screen.x, 0);
-    let start_y = max(rect_top_left_screen.y, 0);
-    let end_x = min(rect_bottom_right_screen.x, buffer.width);
-    let end_y = min(rect_bottom_right_screen.y, buffer.height);
-
-    let mut x = start_x;
-    while x < end_x {
-        let mut y = start_y;
-        while y < end_y {
-            draw_pixel_to_buffer(buffer, x, y, color);
-            y += 1;
-        }
-        x += 1;
-    }
+    canvas.fill_rectangle(rect_top_left_screen, rect_bottom_right_screen, color);
 }
 
 // TODO: Fix circle staying still when moving between y=0 and y=1 (same for x)
-unsafe fn draw_circle(buffer: &mut OffscreenBuffer, camera: Camera, position: Vector2, radius: f32, color: u32) {
-    let screen_pos = world_space_to_screen_space_i32(camera, position);
+fn draw_circle(canvas: &mut Canvas, camera: Camera, position: Vector2, radius: f32, color: u32) {
+    let screen_pos = world_space_to_screen_space(camera, position);
     let screen_radius = (radius * camera.y_scale) as i32;
 
-    let start_x = max(screen_pos.x as i32 - screen_radius, 0) as u32;
-    let start_y = max(screen_pos.y as i32 - screen_radius, 0) as u32;
-    let end_x = min(max(screen_pos.x + screen_radius, 0), buffer.width as i32 - 1) as u32;
-    let end_y = min(max(screen_pos.y + screen_radius, 0), buffer.height as i32 - 1) as u32;
-
-    let mut x = start_x;
-    while x <= end_x {
-        let mut y = start_y;
-        while y <= end_y {
-            let dist = distance_i32(screen_pos, Vector2i32 { x: x as i32, y: y as i32 });
-            if dist <= screen_radius as f32 {
-                draw_pixel_to_buffer(buffer, x, y, color);
-            } else if dist <= screen_radius as f32 + 1.0 {
-                // TODO: Make it so anti-aliasing works properly with transparent circles
-                let dist_dec = dist - (dist as i32) as f32;
-                let alpha = ((1.0 - dist_dec) * 255.0) as u32;
-                let color_with_alpha = (color & 0x00FFFFFF) | (alpha << 24);
-                draw_pixel_to_buffer(buffer, x, y, color_with_alpha);
-            }
-            y += 1;
-        }
-        x += 1;
-    }
+    canvas.fill_circle(screen_pos, screen_radius, color);
 }
 
 fn distance_f32(a: Vector2, b: Vector2) -> f32 {
@@ -714,17 +1113,33 @@ fn distance_f32(a: Vector2, b: Vector2) -> f32 {
     (dx * dx + dy * dy).sqrt()
 }
 
-fn distance_i32(a: Vector2i32, b: Vector2i32) -> f32 {
-    let dx = (a.x as f32 - b.x as f32).abs();
-    let dy = (a.y as f32 - b.y as f32).abs();
-    (dx * dx + dy * dy).sqrt()
+fn draw_curve_fills(canvas: &mut Canvas, camera: Camera, rasterizer: &mut Rasterizer, curves: &[Option<BezierCurve>], fill_colors: &[u32]) {
+    for (curve, fill_color) in curves.iter().zip(fill_colors) {
+        match curve {
+            Some(value) if *fill_color != 0 => {
+                let path = [PathSegment::MoveTo(value.p0), PathSegment::CurveTo(value.p1, value.p2, value.p3), PathSegment::Close];
+                fill_path(rasterizer, camera, &path);
+                rasterizer.fill(canvas, *fill_color, FillRule::NonZero);
+            },
+            _ => {
+                continue;
+            }
+        }
+    }
 }
 
-unsafe fn draw_bezier_curves(buffer: &mut OffscreenBuffer, game_state: &GameState) {
-    for curve in game_state.curves {
-        match curve {
+fn draw_bezier_curves(canvas: &mut Canvas, camera: Camera, rasterizer: &mut Rasterizer, curves: &[Option<BezierCurve>], selected_curve_index: Option<u32>) {
+    for (i, curve) in curves.iter().enumerate() {
+        match *curve {
             Some(value) => {
-                draw_bezier_curve(buffer, game_state.camera, value);
+                let style = if selected_curve_index == Some(i as u32) {
+                    SELECTED_CURVE_STYLE
+                } else {
+                    CURVE_STYLE
+                };
+
+                stroke_bezier_curve(rasterizer, camera, value, style);
+                rasterizer.fill(canvas, 0xFFFFFFFF, FillRule::NonZero);
             },
             None => {
                 continue;
@@ -733,57 +1148,92 @@ unsafe fn draw_bezier_curves(buffer: &mut OffscreenBuffer, game_state: &GameStat
     }
 }
 
-unsafe fn draw_bezier_curve(buffer: &mut OffscreenBuffer, camera: Camera, bezier: BezierCurve) {
-    let mut start = bezier.p0;
-    let mut i = 0.1;
-    while i <= 1.0 {
-        let end = bezier.evaluate(i);
-
-        draw_line(buffer, camera, start, end, 0xFFFFFFFF);
-        start = end;
-
-        i += 0.1;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::mem::offset_of;
+
+    const PERMANENT_STORAGE_SIZE: usize = 1024 * 1024;
+    const TRANSIENT_STORAGE_SIZE: usize = 64 * 1024;
+
+    // Zeroed blocks like the host's, as u64 so GameState's alignment works out
+    fn test_memory(permanent: &mut [u64], transient: &mut [u64]) -> GameMemory {
+        GameMemory {
+            is_initialized: false,
+            permanent_storage_size: size_of_val(permanent) as u64,
+            permanent_storage: permanent.as_mut_ptr() as *mut c_void,
+            transient_storage_size: size_of_val(transient) as u64,
+            transient_storage: transient.as_mut_ptr() as *mut c_void,
+            services: stub_services()
+        }
     }
 
-    draw_line(buffer, camera, start, bezier.p3, 0xFFFFFFFF);
-}
-
-unsafe fn draw_pixel_to_buffer(buffer: &mut OffscreenBuffer, x: u32, y: u32, color: u32) {
-    let mut row: *mut u8 = (*buffer).memory as *mut u8;
-    row = row.offset((*buffer).pitch as isize * y as isize);
+    fn test_curve() -> BezierCurve {
+        BezierCurve::new(Vector2 { x: 1.0, y: 2.0 }, Vector2 { x: 3.0, y: 4.0 }, Vector2 { x: 5.0, y: 6.0 }, Vector2 { x: 7.0, y: 8.0 })
+    }
 
-    let mut pixel: *mut u32 = row as *mut u32;
-    pixel = pixel.offset(x as isize);
+    unsafe fn header(memory: &GameMemory) -> &mut GameStateHeader {
+        &mut *(memory.permanent_storage as *mut GameStateHeader)
+    }
 
-    let alpha = get_alpha(color);
-    if alpha == 1.0 {
-        *pixel = color;
-    } else {
-        *pixel = lerp_color(*pixel, color, alpha);
+    #[test]
+    fn reload_keeps_fields_in_front_of_a_changed_one() {
+        let mut permanent = vec![0u64; PERMANENT_STORAGE_SIZE / 8];
+        let mut transient = vec![0u64; TRANSIENT_STORAGE_SIZE / 8];
+        let mut memory = test_memory(&mut permanent, &mut transient);
+
+        unsafe {
+            let game_state = get_game_state(&mut memory);
+            game_state.curves[1] = Some(test_curve());
+            game_state.curve_fill_colors[1] = 0x803399FF;
+            game_state.camera.x = 12.0;
+            game_state.delta_time = 1.0;
+
+            // As if previous_camera had changed type since the state was written
+            let layout = game_state_layout();
+            let changed = (0..layout.field_count).find(|i| layout.ends[*i] == offset_of!(GameState, previous_camera) + size_of::<Camera>()).unwrap();
+            header(&memory).field_hashes[changed] ^= 1;
+
+            let game_state = get_game_state(&mut memory);
+            assert_eq!(game_state.curves[1].unwrap().p3.x, 7.0);
+            assert_eq!(game_state.curve_fill_colors[1], 0x803399FF);
+            assert_eq!(game_state.camera.x, 12.0);
+            assert_eq!(game_state.delta_time, 0.0);
+            assert_eq!(header(&memory).field_hashes[changed], layout.hashes[changed]);
+        }
     }
-}
 
-fn get_alpha(color: u32) -> f32 {
-    (color >> 24) as f32 / 255.0
-}
+    #[test]
+    fn reload_keeps_everything_when_fields_were_removed_from_the_end() {
+        let mut permanent = vec![0u64; PERMANENT_STORAGE_SIZE / 8];
+        let mut transient = vec![0u64; TRANSIENT_STORAGE_SIZE / 8];
+        let mut memory = test_memory(&mut permanent, &mut transient);
 
-fn lerp_color(a: u32, b: u32, t: f32) -> u32 {
-    let a_red = (a >> 16) as u8;
-    let a_green = (a >> 8) as u8;
-    let a_blue = a as u8;
+        unsafe {
+            get_game_state(&mut memory).curves[1] = Some(test_curve());
 
-    let b_red = (b >> 16) as u8;
-    let b_green = (b >> 8) as u8;
-    let b_blue = b as u8;
+            let header = header(&memory);
+            header.field_hashes[header.field_count as usize] = 1;
+            header.field_count += 1;
 
-    let red = (a_red as f32 + t * (b_red as f32 - a_red as f32)) as u8;
-    let green = (a_green as f32 + t * (b_green as f32 - a_green as f32)) as u8;
-    let blue = (a_blue as f32 + t * (b_blue as f32 - a_blue as f32)) as u8;
+            let game_state = get_game_state(&mut memory);
+            assert_eq!(game_state.curves[1].unwrap().p0.y, 2.0);
+        }
+    }
 
-    // NOTE: This version uses fewer variables, if a performance concern
-    //let red = (a >> 16) as u8 + (t * ((b >> 16) as u8 - (a >> 16) as u8) as f32) as u8;
-    //let green = (a >> 8) as u8 + (t * ((b >> 8) as u8 - (a >> 8) as u8) as f32) as u8;
-    //let blue = a as u8 + (t * (b as u8 - a as u8) as f32) as u8;
+    #[test]
+    fn reload_starts_over_on_another_version() {
+        let mut permanent = vec![0u64; PERMANENT_STORAGE_SIZE / 8];
+        let mut transient = vec![0u64; TRANSIENT_STORAGE_SIZE / 8];
+        let mut memory = test_memory(&mut permanent, &mut transient);
 
-    ((red as u32) << 16) | ((green as u32) << 8) | blue as u32
+        unsafe {
+            get_game_state(&mut memory).curves[1] = Some(test_curve());
+            header(&memory).version -= 1;
+
+            let game_state = get_game_state(&mut memory);
+            assert!(game_state.curves[1].is_none());
+            assert_eq!(game_state.target_camera_height, 9.0);
+        }
+    }
 }
@@ -0,0 +1,70 @@
+use crate::{world_space_to_screen_space_f32, BezierCurve, Camera, Rasterizer, Vector2, CURVE_TOLERANCE, MAX_CURVE_SEGMENTS};
+
+// One step of a path, each carries on from where the one before it ended.
+// A path is a slice of these, made of one or more contours.
+#[derive(Clone, Copy, Debug)]
+pub enum PathSegment {
+    // Starts a new contour
+    MoveTo(Vector2),
+    LineTo(Vector2),
+    // A BezierCurve from the current point, through the two control points, to the last one
+    CurveTo(Vector2, Vector2, Vector2),
+    // Back to where the contour started with a straight line
+    Close
+}
+
+// Adds the outline of a world space path to rasterizer, fill it to draw the inside.
+// Contours that aren't closed get closed with a straight line, like Close would.
+pub fn fill_path(rasterizer: &mut Rasterizer, camera: Camera, path: &[PathSegment]) {
+    let to_screen = |point: Vector2| world_space_to_screen_space_f32(camera, point);
+
+    // Where the contour started and where the last segment ended, in screen space.
+    // Like SVG, a path that doesn't start with MoveTo starts at the origin, and one that carries on
+    // after Close starts a new contour where the last one started.
+    let mut contour_start: Option<Vector2> = None;
+    let mut current = to_screen(Vector2::zero());
+
+    for segment in path {
+        match *segment {
+            PathSegment::MoveTo(point) => {
+                if let Some(start) = contour_start {
+                    rasterizer.line(current, start);
+                }
+                current = to_screen(point);
+                contour_start = Some(current);
+            },
+            PathSegment::LineTo(point) => {
+                contour_start.get_or_insert(current);
+                let end = to_screen(point);
+                rasterizer.line(current, end);
+                current = end;
+            },
+            PathSegment::CurveTo(control_1, control_2, point) => {
+                contour_start.get_or_insert(current);
+                // Screen space is just scaled and moved, so the curve through the moved control points is the same
+                // curve, and flattening it there makes the tolerance pixels
+                let curve = BezierCurve::new(current, to_screen(control_1), to_screen(control_2), to_screen(point));
+                let mut points = [Vector2::zero(); MAX_CURVE_SEGMENTS + 1];
+                let point_count = curve.flatten(CURVE_TOLERANCE, &mut points);
+
+                let mut i = 1;
+                while i < point_count {
+                    rasterizer.line(points[i - 1], points[i]);
+                    i += 1;
+                }
+
+                current = curve.p3;
+            },
+            PathSegment::Close => {
+                if let Some(start) = contour_start.take() {
+                    rasterizer.line(current, start);
+                    current = start;
+                }
+            }
+        }
+    }
+
+    if let Some(start) = contour_start {
+        rasterizer.line(current, start);
+    }
+}
@@ -0,0 +1,338 @@
+use crate::{Canvas, Vector2};
+
+// Which parts of overlapping outlines count as inside
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum FillRule {
+    // Anywhere the outlines go around more times one way than the other
+    NonZero,
+    // Anywhere an odd number of outlines go around, so a shape inside another cuts a hole in it
+    EvenOdd
+}
+
+// Fills outlines made of straight edges, anti-aliased by how much of each pixel is inside.
+// Works like the font rasterizers do: every edge adds its signed area to the cells it passes
+// through, and summing a row from the left gives how much each pixel is covered.
+// Edges can be added in any order and from any number of overlapping polygons, as long as each
+// polygon is closed.
+// Overlapping polygons add up, so where their anti-aliased edges share a pixel it comes out
+// too covered. Shapes built from overlapping pieces merge each piece on its own instead.
+pub struct Rasterizer<'a> {
+    // (width + 2) cells per row, the two extra catch the area of edges on or past the right side
+    cells: &'a mut [f32],
+    // One per pixel, what merge has covered since the last fill
+    coverage: &'a mut [f32],
+    width: u32,
+    height: u32,
+    // Rows and columns edges have touched since the last fill or merge, empty when min > max
+    min_x: i32,
+    max_x: i32,
+    min_y: i32,
+    max_y: i32,
+    // Pixels merge has covered since the last fill, empty when min > max
+    merged_min_x: i32,
+    merged_max_x: i32,
+    merged_min_y: i32,
+    merged_max_y: i32
+}
+
+impl<'a> Rasterizer<'a> {
+    // How many cells a rasterizer for a width * height canvas needs, for the edges and for merge
+    pub fn cell_count(width: u32, height: u32) -> usize {
+        (width as usize + 2) * height as usize + width as usize * height as usize
+    }
+
+    // cells has to be at least cell_count long and all zeroes
+    pub fn new(cells: &'a mut [f32], width: u32, height: u32) -> Rasterizer<'a> {
+        assert!(cells.len() >= Rasterizer::cell_count(width, height), "Rasterizer needs {} cells but only has {}",
+            Rasterizer::cell_count(width, height), cells.len());
+
+        let (cells, coverage) = cells.split_at_mut((width as usize + 2) * height as usize);
+
+        Rasterizer {
+            cells,
+            coverage,
+            width,
+            height,
+            min_x: i32::MAX,
+            max_x: i32::MIN,
+            min_y: i32::MAX,
+            max_y: i32::MIN,
+            merged_min_x: i32::MAX,
+            merged_max_x: i32::MIN,
+            merged_min_y: i32::MAX,
+            merged_max_y: i32::MIN
+        }
+    }
+
+    // One edge of a polygon, in the same coordinates as the canvas
+    pub fn line(&mut self, a: Vector2, b: Vector2) {
+        if !(a.x.is_finite() && a.y.is_finite() && b.x.is_finite() && b.y.is_finite()) {
+            return;
+        }
+
+        // Whatever is left of the canvas still covers everything right of it on the same row,
+        // so those parts get squashed onto the left side. Anything right of it doesn't cover
+        // anything visible and gets squashed onto the right side, where the extra cells are.
+        let width = self.width as f32;
+        // Up to three pieces, one on each side of the canvas and one inside it
+        let mut pieces = [a; 6];
+        let mut piece_count = 0;
+        let mut start = a;
+        let edges = if a.x <= b.x { [0.0, width] } else { [width, 0.0] };
+        for edge_x in edges {
+            let crosses = (start.x < edge_x) != (b.x < edge_x) && start.x != edge_x && b.x != edge_x;
+            if crosses {
+                let t = (edge_x - start.x) / (b.x - start.x);
+                let crossing = Vector2 { x: edge_x, y: start.y + (b.y - start.y) * t };
+                pieces[piece_count] = start;
+                pieces[piece_count + 1] = crossing;
+                piece_count += 2;
+                start = crossing;
+            }
+        }
+        pieces[piece_count] = start;
+        pieces[piece_count + 1] = b;
+        piece_count += 2;
+
+        let mut i = 0;
+        while i < piece_count {
+            let clamp = |point: Vector2| Vector2 { x: point.x.clamp(0.0, width), y: point.y };
+            self.accumulate(clamp(pieces[i]), clamp(pieces[i + 1]));
+            i += 2;
+        }
+    }
+
+    // Blends color into every pixel by how much of it is inside the edges and merged pieces so far,
+    // then starts over
+    pub fn fill(&mut self, canvas: &mut Canvas, color: u32, rule: FillRule) {
+        assert!(canvas.width() == self.width && canvas.height() == self.height, "Rasterizer and canvas sizes differ");
+
+        let width = self.width as usize;
+        let coverage = &mut *self.coverage;
+        resolve_edges(self.cells, self.width, (self.min_x, self.max_x, self.min_y, self.max_y), rule, |x, y, edge_coverage| {
+            let merged = &mut coverage[y as usize * width + x as usize];
+            canvas.blend_pixel_coverage(x, y, color, edge_coverage.max(*merged));
+            *merged = 0.0;
+        });
+
+        // Merged pixels the edges didn't touch, the ones they did were zeroed above
+        let mut y = self.merged_min_y;
+        while y <= self.merged_max_y {
+            let mut x = self.merged_min_x;
+            while x <= self.merged_max_x {
+                let merged = &mut coverage[y as usize * width + x as usize];
+                if *merged > 0.0 {
+                    canvas.blend_pixel_coverage(x, y, color, *merged);
+                    *merged = 0.0;
+                }
+                x += 1;
+            }
+            y += 1;
+        }
+
+        self.clear_edge_bounds();
+        self.merged_min_x = i32::MAX;
+        self.merged_max_x = i32::MIN;
+        self.merged_min_y = i32::MAX;
+        self.merged_max_y = i32::MIN;
+    }
+
+    // Turns the edges so far into coverage and keeps it until the next fill, taking whichever is
+    // higher where it overlaps earlier merges. For shapes made of overlapping pieces, like strokes,
+    // so the edges of one don't add to the coverage of another.
+    pub fn merge(&mut self, rule: FillRule) {
+        if self.min_x > self.max_x {
+            return;
+        }
+
+        let width = self.width as usize;
+        let coverage = &mut *self.coverage;
+        resolve_edges(self.cells, self.width, (self.min_x, self.max_x, self.min_y, self.max_y), rule, |x, y, edge_coverage| {
+            let merged = &mut coverage[y as usize * width + x as usize];
+            *merged = merged.max(edge_coverage);
+        });
+
+        self.merged_min_x = self.merged_min_x.min(self.min_x);
+        self.merged_max_x = self.merged_max_x.max(self.max_x.min(self.width as i32 - 1));
+        self.merged_min_y = self.merged_min_y.min(self.min_y);
+        self.merged_max_y = self.merged_max_y.max(self.max_y);
+        self.clear_edge_bounds();
+    }
+
+    fn clear_edge_bounds(&mut self) {
+        self.min_x = i32::MAX;
+        self.max_x = i32::MIN;
+        self.min_y = i32::MAX;
+        self.max_y = i32::MIN;
+    }
+
+    // Adds the area left of a to b in each row it passes through, x has to be between 0 and width
+    fn accumulate(&mut self, a: Vector2, b: Vector2) {
+        if a.y == b.y {
+            return;
+        }
+
+        // Downwards edges add, upwards ones take away
+        let (direction, top, bottom) = if a.y < b.y { (1.0, a, b) } else { (-1.0, b, a) };
+        let dx_dy = (bottom.x - top.x) / (bottom.y - top.y);
+
+        let start_y = top.y.max(0.0).floor() as i32;
+        let end_y = bottom.y.min(self.height as f32).ceil() as i32;
+        if start_y >= end_y {
+            return;
+        }
+
+        let stride = self.width as usize + 2;
+
+        let mut y = start_y;
+        while y < end_y {
+            let row = y as usize * stride;
+
+            // The part of the edge inside this row
+            let row_top = top.y.max(y as f32);
+            let row_bottom = bottom.y.min(y as f32 + 1.0);
+            let x_top = top.x + (row_top - top.y) * dx_dy;
+            let x_bottom = top.x + (row_bottom - top.y) * dx_dy;
+            let height = (row_bottom - row_top) * direction;
+
+            let (x0, x1) = if x_top < x_bottom { (x_top, x_bottom) } else { (x_bottom, x_top) };
+            let x0_floor = x0.floor();
+            let x0_cell = x0_floor as usize;
+            let x1_ceil = x1.ceil();
+            let x1_cell = x1_ceil as usize;
+
+            if x1_cell <= x0_cell + 1 {
+                // Inside a single column, split between it and the one to its right
+                let x_middle = 0.5 * (x_top + x_bottom) - x0_floor;
+                self.cells[row + x0_cell] += height - height * x_middle;
+                self.cells[row + x0_cell + 1] += height * x_middle;
+            } else {
+                // Across several columns, the area under the edge in each of them
+                let slope = (x1 - x0).recip();
+                let x0_fraction = x0 - x0_floor;
+                let first_area = 0.5 * slope * (1.0 - x0_fraction) * (1.0 - x0_fraction);
+                let x1_fraction = x1 - x1_ceil + 1.0;
+                let last_area = 0.5 * slope * x1_fraction * x1_fraction;
+
+                self.cells[row + x0_cell] += height * first_area;
+                if x1_cell == x0_cell + 2 {
+                    self.cells[row + x0_cell + 1] += height * (1.0 - first_area - last_area);
+                } else {
+                    let second_area = slope * (1.5 - x0_fraction);
+                    self.cells[row + x0_cell + 1] += height * (second_area - first_area);
+
+                    let mut x = x0_cell + 2;
+                    while x < x1_cell - 1 {
+                        self.cells[row + x] += height * slope;
+                        x += 1;
+                    }
+
+                    let area_before_last = second_area + (x1_cell - x0_cell - 3) as f32 * slope;
+                    self.cells[row + x1_cell - 1] += height * (1.0 - area_before_last - last_area);
+                }
+                self.cells[row + x1_cell] += height * last_area;
+            }
+
+            self.min_x = self.min_x.min(x0_cell as i32);
+            self.max_x = self.max_x.max(x1_cell as i32 + 1);
+            y += 1;
+        }
+
+        self.min_y = self.min_y.min(start_y);
+        self.max_y = self.max_y.max(end_y - 1);
+    }
+}
+
+// Sums the cells inside bounds (min_x, max_x, min_y, max_y) row by row, hands pixel how much of each
+// pixel is covered and leaves the cells zeroed
+fn resolve_edges(cells: &mut [f32], width: u32, bounds: (i32, i32, i32, i32), rule: FillRule, mut pixel: impl FnMut(i32, i32, f32)) {
+    let (min_x, max_x, min_y, max_y) = bounds;
+    let stride = width as usize + 2;
+    let end_x = max_x.min(width as i32 - 1);
+
+    let mut y = min_y;
+    while y <= max_y {
+        let row = y as usize * stride;
+        let mut winding: f32 = 0.0;
+
+        // Nothing left of min_x was touched, so the sum starts at zero there
+        let mut x = min_x;
+        while x <= end_x {
+            // How many times the outlines go around this pixel, a fraction along their edges
+            winding += cells[row + x as usize];
+            let coverage = match rule {
+                FillRule::NonZero => winding.abs().min(1.0),
+                FillRule::EvenOdd => {
+                    let odd = winding.abs() % 2.0;
+                    if odd > 1.0 { 2.0 - odd } else { odd }
+                }
+            };
+            pixel(x, y, coverage);
+            x += 1;
+        }
+
+        cells[row + min_x as usize..=row + max_x as usize].fill(0.0);
+        y += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn square(rasterizer: &mut Rasterizer, left: f32, top: f32, size: f32) {
+        let corners = [
+            Vector2 { x: left, y: top },
+            Vector2 { x: left + size, y: top },
+            Vector2 { x: left + size, y: top + size },
+            Vector2 { x: left, y: top + size }
+        ];
+        let mut i = 0;
+        while i < 4 {
+            rasterizer.line(corners[i], corners[(i + 1) % 4]);
+            i += 1;
+        }
+    }
+
+    // Red channel of white filled over black
+    fn red(canvas: &Canvas, x: i32, y: i32) -> u32 {
+        (canvas.get_pixel(x, y).unwrap() >> 16) & 0xFF
+    }
+
+    #[test]
+    fn merged_pieces_dont_add_up_along_a_shared_edge() {
+        let mut pixels = [0xFF000000u32; 8 * 8];
+        let mut canvas = Canvas::new(&mut pixels, 8, 8, 8);
+        let mut cells = vec![0.0; Rasterizer::cell_count(8, 8)];
+        let mut rasterizer = Rasterizer::new(&mut cells, 8, 8);
+
+        // Two squares on top of each other, both with their left edge halfway through column 1
+        square(&mut rasterizer, 1.5, 1.0, 4.0);
+        rasterizer.merge(FillRule::NonZero);
+        square(&mut rasterizer, 1.5, 2.0, 4.0);
+        rasterizer.merge(FillRule::NonZero);
+        rasterizer.fill(&mut canvas, 0xFFFFFFFF, FillRule::NonZero);
+
+        assert!((red(&canvas, 1, 3) as i32 - 128).abs() <= 1);
+        assert_eq!(red(&canvas, 3, 3), 255);
+        assert_eq!(red(&canvas, 3, 6), 0);
+    }
+
+    #[test]
+    fn pieces_sharing_an_edge_add_up_to_fully_covered() {
+        let mut pixels = [0xFF000000u32; 8 * 8];
+        let mut canvas = Canvas::new(&mut pixels, 8, 8, 8);
+        let mut cells = vec![0.0; Rasterizer::cell_count(8, 8)];
+        let mut rasterizer = Rasterizer::new(&mut cells, 8, 8);
+
+        // Side by side, meeting halfway through column 3
+        square(&mut rasterizer, 0.0, 0.0, 3.5);
+        square(&mut rasterizer, 3.5, 0.0, 3.5);
+        rasterizer.fill(&mut canvas, 0xFFFFFFFF, FillRule::NonZero);
+
+        assert_eq!(red(&canvas, 3, 1), 255);
+        // And the rasterizer starts over after a fill
+        rasterizer.fill(&mut canvas, 0xFF000000, FillRule::NonZero);
+        assert_eq!(red(&canvas, 3, 1), 255);
+    }
+}
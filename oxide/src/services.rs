@@ -0,0 +1,90 @@
+use crate::MemoryArena;
+
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LogLevel {
+    Debug,
+    Info,
+    Warning,
+    Error
+}
+
+// What the host does on the game's behalf, so the game itself never talks to the OS.
+// Strings are passed as pointer and length, they don't need to be null terminated.
+// A test host can hand over stubs instead.
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct PlatformServices {
+    // -1 if the file can't be read
+    pub get_file_size: extern "C" fn(path: *const u8, path_length: usize) -> i64,
+    // Fills destination with the whole file, fails if the file isn't exactly destination_size bytes
+    pub read_entire_file: extern "C" fn(path: *const u8, path_length: usize, destination: *mut u8, destination_size: u64) -> bool,
+    pub write_entire_file: extern "C" fn(path: *const u8, path_length: usize, data: *const u8, data_size: u64) -> bool,
+    pub log: extern "C" fn(level: LogLevel, message: *const u8, message_length: usize),
+    // Monotonic milliseconds since some point at startup
+    pub now: extern "C" fn() -> f64,
+    // Hides the cursor and keeps it in the window, so the mouse only reports movement through
+    // MouseState::delta and never runs into the screen edge. Takes effect from the next frame.
+    pub set_relative_mouse: extern "C" fn(enabled: bool)
+}
+
+impl PlatformServices {
+    // The file's contents live in arena, so for transient use they're gone next frame
+    pub fn read_entire_file<'a>(&self, arena: &'a mut MemoryArena, path: &str) -> Option<&'a mut [u8]> {
+        let size = (self.get_file_size)(path.as_ptr(), path.len());
+        if size < 0 {
+            return None;
+        }
+
+        let contents = arena.push_slice(size as usize, 0u8);
+        if (self.read_entire_file)(path.as_ptr(), path.len(), contents.as_mut_ptr(), size as u64) {
+            Some(contents)
+        } else {
+            None
+        }
+    }
+
+    pub fn write_entire_file(&self, path: &str, data: &[u8]) -> bool {
+        (self.write_entire_file)(path.as_ptr(), path.len(), data.as_ptr(), data.len() as u64)
+    }
+
+    pub fn log(&self, level: LogLevel, message: &str) {
+        (self.log)(level, message.as_ptr(), message.len());
+    }
+
+    pub fn now(&self) -> f64 {
+        (self.now)()
+    }
+
+    pub fn set_relative_mouse(&self, enabled: bool) {
+        (self.set_relative_mouse)(enabled);
+    }
+}
+
+// log!(services, LogLevel::Info, "Loaded {} curves", count)
+#[macro_export]
+macro_rules! log {
+    ($services:expr, $level:expr, $($arg:tt)*) => {
+        $services.log($level, &format!($($arg)*))
+    };
+}
+
+// What a test host hands over, there are no files and logging goes nowhere
+#[cfg(test)]
+pub(crate) fn stub_services() -> PlatformServices {
+    extern "C" fn get_file_size(_path: *const u8, _path_length: usize) -> i64 { -1 }
+    extern "C" fn read_entire_file(_path: *const u8, _path_length: usize, _destination: *mut u8, _destination_size: u64) -> bool { false }
+    extern "C" fn write_entire_file(_path: *const u8, _path_length: usize, _data: *const u8, _data_size: u64) -> bool { false }
+    extern "C" fn log(_level: LogLevel, _message: *const u8, _message_length: usize) {}
+    extern "C" fn now() -> f64 { 0.0 }
+    extern "C" fn set_relative_mouse(_enabled: bool) {}
+
+    PlatformServices {
+        get_file_size,
+        read_entire_file,
+        write_entire_file,
+        log,
+        now,
+        set_relative_mouse
+    }
+}
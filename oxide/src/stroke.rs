@@ -0,0 +1,247 @@
+use crate::{distance_f32, world_space_to_screen_space_f32, BezierCurve, Camera, FillRule, Rasterizer, Vector2, CURVE_TOLERANCE, MAX_CURVE_SEGMENTS};
+
+// How thick a stroke is, in world units so it scales with the zoom or in pixels so it doesn't
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum StrokeWidth {
+    World(f32),
+    Screen(f32)
+}
+
+// What goes where two segments meet
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LineJoin {
+    // The outer edges carried on until they meet, a bevel when that's further out than the miter limit
+    Miter,
+    Round,
+    // The outer corners cut off straight
+    Bevel
+}
+
+// What goes on the two open ends
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum LineCap {
+    // Stops right at the end point
+    Butt,
+    Round,
+    // Carries on half the width past the end point
+    Square
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct StrokeStyle {
+    pub width: StrokeWidth,
+    pub join: LineJoin,
+    pub cap: LineCap
+}
+
+// Same as SVG's default, miters get cut off once they're 4 times as long as the stroke is wide
+const MITER_LIMIT: f32 = 4.0;
+// Segments shorter than this many pixels don't have a direction worth trusting and are skipped
+const MIN_SEGMENT_LENGTH: f32 = 1e-4;
+// How far in pixels the polygon standing in for a round join or cap can be from the real circle
+const ROUND_TOLERANCE: f32 = 0.1;
+const MAX_ROUND_SEGMENTS: u32 = 256;
+
+// Merges a polyline stroked with style into rasterizer, fill it to draw it.
+// The stroke is made of pieces, one per segment, join and cap. The straight ones only share
+// edges with each other, so they're added up together, which gets the pixels along those edges
+// exactly right. Round joins and caps overlap the segments along their sides, so they're merged
+// one at a time afterwards and where their anti-aliased edges meet the segments' the coverage
+// doesn't add up to a darker seam.
+pub fn stroke_polyline(rasterizer: &mut Rasterizer, camera: Camera, points: &[Vector2], style: StrokeStyle) {
+    if points.is_empty() {
+        return;
+    }
+
+    let half_width = match style.width {
+        StrokeWidth::World(width) => width * camera.y_scale,
+        StrokeWidth::Screen(width) => width
+    } * 0.5;
+
+    if half_width <= 0.0 || !half_width.is_finite() {
+        return;
+    }
+
+    add_pieces(rasterizer, camera, points, half_width, style, false);
+    rasterizer.merge(FillRule::NonZero);
+    add_pieces(rasterizer, camera, points, half_width, style, true);
+}
+
+// Splits the curve into as many straight segments as it takes to look smooth at the camera's zoom
+// and strokes those
+pub fn stroke_bezier_curve(rasterizer: &mut Rasterizer, camera: Camera, curve: BezierCurve, style: StrokeStyle) {
+    let mut points = [Vector2::zero(); MAX_CURVE_SEGMENTS + 1];
+    let point_count = curve.flatten(CURVE_TOLERANCE / camera.y_scale, &mut points);
+    stroke_polyline(rasterizer, camera, &points[..point_count], style);
+}
+
+// Goes along the polyline adding either the round joins and caps or everything else
+fn add_pieces(rasterizer: &mut Rasterizer, camera: Camera, points: &[Vector2], half_width: f32, style: StrokeStyle, round: bool) {
+    let screen_point = |i: usize| world_space_to_screen_space_f32(camera, points[i]);
+    let round_join = style.join == LineJoin::Round;
+    let round_cap = style.cap == LineCap::Round;
+
+    // Where the first segment starts and which way it goes, for the cap on that end
+    let mut first: Option<(Vector2, Vector2)> = None;
+    let mut previous_direction: Option<Vector2> = None;
+    let mut start = screen_point(0);
+
+    let mut i = 1;
+    while i < points.len() {
+        let end = screen_point(i);
+        i += 1;
+
+        let length = distance_f32(start, end);
+        if length < MIN_SEGMENT_LENGTH || !length.is_finite() {
+            continue;
+        }
+
+        let direction = Vector2 { x: (end.x - start.x) / length, y: (end.y - start.y) / length };
+
+        match previous_direction {
+            Some(previous_direction) if round_join == round => add_join(rasterizer, start, previous_direction, direction, half_width, style.join),
+            Some(_) => {},
+            None => first = Some((start, direction))
+        }
+
+        if !round {
+            let normal = left_normal(direction) * half_width;
+            add_polygon(rasterizer, &[start + normal, end + normal, end - normal, start - normal]);
+        }
+
+        previous_direction = Some(direction);
+        start = end;
+    }
+
+    if round_cap != round {
+        return;
+    }
+
+    match (first, previous_direction) {
+        (Some((first_point, first_direction)), Some(last_direction)) => {
+            add_cap(rasterizer, first_point, first_direction * -1.0, half_width, style.cap);
+            add_cap(rasterizer, start, last_direction, half_width, style.cap);
+        },
+        // Nothing but a point, which only the caps give a shape
+        _ => match style.cap {
+            LineCap::Butt => {},
+            LineCap::Round => add_circle(rasterizer, start, half_width),
+            LineCap::Square => {
+                let corner = Vector2 { x: half_width, y: half_width };
+                let other_corner = Vector2 { x: half_width, y: -half_width };
+                add_polygon(rasterizer, &[start - corner, start + other_corner, start + corner, start - other_corner]);
+            }
+        }
+    }
+}
+
+fn add_join(rasterizer: &mut Rasterizer, point: Vector2, direction_in: Vector2, direction_out: Vector2, half_width: f32, join: LineJoin) {
+    let cross = direction_in.x * direction_out.y - direction_in.y * direction_out.x;
+    let dot = direction_in.x * direction_out.x + direction_in.y * direction_out.y;
+
+    // Carries straight on, the two segments already meet without a gap
+    if cross.abs() < 1e-6 && dot > 0.0 {
+        return;
+    }
+
+    if join == LineJoin::Round {
+        add_circle(rasterizer, point, half_width);
+        return;
+    }
+
+    // The gap is on the outside of the turn, away from the side it turns towards
+    let side = if cross > 0.0 { -1.0 } else { 1.0 };
+    let normal_in = left_normal(direction_in) * side;
+    let normal_out = left_normal(direction_out) * side;
+    let corner_in = point + normal_in * half_width;
+    let corner_out = point + normal_out * half_width;
+
+    if join == LineJoin::Miter {
+        let middle = normal_in + normal_out;
+        let middle_length = (middle.x * middle.x + middle.y * middle.y).sqrt();
+
+        // 1 / cos of half the angle between the normals, how much longer the miter is than half the width
+        let miter_ratio = if middle_length > 1e-6 {
+            middle_length / (middle.x * normal_in.x + middle.y * normal_in.y)
+        } else {
+            f32::INFINITY
+        };
+
+        if miter_ratio <= MITER_LIMIT {
+            let tip = point + middle * (miter_ratio * half_width / middle_length);
+            add_polygon(rasterizer, &[point, corner_in, tip, corner_out]);
+            return;
+        }
+    }
+
+    add_polygon(rasterizer, &[point, corner_in, corner_out]);
+}
+
+// direction points away from the stroke
+fn add_cap(rasterizer: &mut Rasterizer, point: Vector2, direction: Vector2, half_width: f32, cap: LineCap) {
+    match cap {
+        LineCap::Butt => {},
+        LineCap::Round => add_circle(rasterizer, point, half_width),
+        LineCap::Square => {
+            let normal = left_normal(direction) * half_width;
+            let ahead = direction * half_width;
+            add_polygon(rasterizer, &[point + normal, point + normal + ahead, point - normal + ahead, point - normal]);
+        }
+    }
+}
+
+// With enough sides that it doesn't look like a polygon, merged on its own
+fn add_circle(rasterizer: &mut Rasterizer, center: Vector2, radius: f32) {
+    let segment_count = if radius <= ROUND_TOLERANCE {
+        4
+    } else {
+        // Each side's middle is radius * cos(half the angle) from the center
+        let max_angle = 2.0 * (1.0 - ROUND_TOLERANCE / radius).acos();
+        ((std::f32::consts::TAU / max_angle).ceil() as u32).clamp(4, MAX_ROUND_SEGMENTS)
+    };
+
+    let point_at = |i: u32| {
+        let angle = i as f32 * std::f32::consts::TAU / segment_count as f32;
+        Vector2 { x: center.x + radius * angle.cos(), y: center.y + radius * angle.sin() }
+    };
+
+    let mut previous = point_at(0);
+    let mut i = 1;
+    while i <= segment_count {
+        let next = if i == segment_count { point_at(0) } else { point_at(i) };
+        rasterizer.line(previous, next);
+        previous = next;
+        i += 1;
+    }
+
+    rasterizer.merge(FillRule::NonZero);
+}
+
+// A closed convex polygon, turned around if need be so its area is positive like every other
+// piece's. Pieces that share an edge then cancel out along it.
+fn add_polygon(rasterizer: &mut Rasterizer, points: &[Vector2]) {
+    let mut area = 0.0;
+    let mut i = 0;
+    while i < points.len() {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        area += a.x * b.y - b.x * a.y;
+        i += 1;
+    }
+
+    let mut i = 0;
+    while i < points.len() {
+        let a = points[i];
+        let b = points[(i + 1) % points.len()];
+        if area >= 0.0 {
+            rasterizer.line(a, b);
+        } else {
+            rasterizer.line(b, a);
+        }
+        i += 1;
+    }
+}
+
+fn left_normal(direction: Vector2) -> Vector2 {
+    Vector2 { x: -direction.y, y: direction.x }
+}
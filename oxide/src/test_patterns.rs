@@ -0,0 +1,336 @@
+use crate::*;
+
+// Fixed drawings that exercise the canvas primitives, for checking rendering against reference
+// images. Only built with the test-patterns feature, main's tests turn it on.
+pub const TEST_PATTERN_NAMES: [&str; 4] = ["lines", "strokes", "fills", "curves"];
+
+// False if there's no pattern called name
+pub fn draw_test_pattern(name: &str, canvas: &mut Canvas) -> bool {
+    match name {
+        "lines" => draw_lines_pattern(canvas),
+        "strokes" => draw_strokes_pattern(canvas),
+        "fills" => draw_fills_pattern(canvas),
+        "curves" => draw_curves_pattern(canvas),
+        _ => return false
+    }
+
+    true
+}
+
+// Meant for a 128x128 canvas
+fn draw_lines_pattern(canvas: &mut Canvas) {
+    canvas.clear(0xFF000000);
+
+    // A star through every octant, centered off the pixel grid so the endpoints land at
+    // different places inside their pixels
+    let center = Vector2 { x: 40.3, y: 40.7 };
+    let mut i = 0;
+    while i < 16 {
+        let angle = i as f32 * std::f32::consts::TAU / 16.0 + 0.1;
+        let end = Vector2 { x: center.x + angle.cos() * 30.0, y: center.y + angle.sin() * 30.0 };
+        canvas.draw_line(center, end, 0xFFFFFFFF);
+        i += 1;
+    }
+
+    // Exactly horizontal, vertical and diagonal, on and between pixel centers
+    canvas.draw_line(Vector2 { x: 80.5, y: 10.5 }, Vector2 { x: 120.5, y: 10.5 }, 0xFFFF8000);
+    canvas.draw_line(Vector2 { x: 80.0, y: 14.0 }, Vector2 { x: 120.0, y: 14.0 }, 0xFFFF8000);
+    canvas.draw_line(Vector2 { x: 84.5, y: 20.5 }, Vector2 { x: 84.5, y: 60.5 }, 0xFF00FF80);
+    canvas.draw_line(Vector2 { x: 88.0, y: 20.0 }, Vector2 { x: 88.0, y: 60.0 }, 0xFF00FF80);
+    canvas.draw_line(Vector2 { x: 92.5, y: 20.5 }, Vector2 { x: 122.5, y: 50.5 }, 0xFF0080FF);
+
+    // Short lines that start and end inside a pixel or two
+    canvas.draw_line(Vector2 { x: 100.2, y: 56.3 }, Vector2 { x: 100.8, y: 56.6 }, 0xFFFFFFFF);
+    canvas.draw_line(Vector2 { x: 104.2, y: 56.3 }, Vector2 { x: 106.9, y: 57.8 }, 0xFFFFFFFF);
+    canvas.draw_line(Vector2 { x: 110.5, y: 56.5 }, Vector2 { x: 110.5, y: 56.5 }, 0xFFFFFFFF);
+
+    // Translucent over opaque
+    canvas.draw_line(Vector2 { x: 8.0, y: 80.0 }, Vector2 { x: 120.0, y: 92.0 }, 0xFFFFFFFF);
+    canvas.draw_line(Vector2 { x: 8.0, y: 92.0 }, Vector2 { x: 120.0, y: 80.0 }, 0x80FF0000);
+
+    // Crossing the edges, and far enough out that only the clipping keeps them sane
+    canvas.draw_line(Vector2 { x: -20.0, y: 100.0 }, Vector2 { x: 40.0, y: 140.0 }, 0xFFFFFF00);
+    canvas.draw_line(Vector2 { x: 100.0, y: 90.0 }, Vector2 { x: 150.0, y: 130.0 }, 0xFFFFFF00);
+    canvas.draw_line(Vector2 { x: -1.0e6, y: 110.3 }, Vector2 { x: 1.0e6, y: 118.7 }, 0xFF00FFFF);
+    canvas.draw_line(Vector2 { x: 64.2, y: -1.0e6 }, Vector2 { x: 70.6, y: 1.0e6 }, 0xFF00FFFF);
+    canvas.draw_line(Vector2 { x: 0.0, y: 127.5 }, Vector2 { x: 128.0, y: 127.5 }, 0xFFFF00FF);
+
+    // Entirely outside, and not even numbers, neither should touch anything
+    canvas.draw_line(Vector2 { x: -50.0, y: -50.0 }, Vector2 { x: -10.0, y: 200.0 }, 0xFFFF0000);
+    canvas.draw_line(Vector2 { x: f32::NAN, y: 10.0 }, Vector2 { x: 60.0, y: 10.0 }, 0xFFFF0000);
+    canvas.draw_line(Vector2 { x: 10.0, y: 10.0 }, Vector2 { x: f32::INFINITY, y: 60.0 }, 0xFFFF0000);
+}
+
+// Meant for a 128x128 canvas
+fn draw_strokes_pattern(canvas: &mut Canvas) {
+    canvas.clear(0xFF000000);
+
+    let mut cells = vec![0.0; Rasterizer::cell_count(canvas.width(), canvas.height())];
+    let mut rasterizer = Rasterizer::new(&mut cells, canvas.width(), canvas.height());
+    let camera = pixel_camera(canvas);
+
+    // Every join on a zigzag with a sharp and a shallow corner, every cap on a short line
+    let zigzag = [
+        Vector2 { x: 6.0, y: 30.0 },
+        Vector2 { x: 16.0, y: 8.0 },
+        Vector2 { x: 22.0, y: 30.0 },
+        Vector2 { x: 36.0, y: 22.0 }
+    ];
+    let joins = [LineJoin::Miter, LineJoin::Round, LineJoin::Bevel];
+    let caps = [LineCap::Butt, LineCap::Round, LineCap::Square];
+
+    let mut i = 0;
+    while i < 3 {
+        let offset = i as f32 * 40.0;
+        let points = zigzag.map(|point| Vector2 { x: point.x + offset, y: point.y });
+        let style = StrokeStyle { width: StrokeWidth::Screen(5.0), join: joins[i], cap: LineCap::Butt };
+        stroke_polyline(&mut rasterizer, camera, &points, style);
+        rasterizer.fill(canvas, 0xFFFFFFFF, FillRule::NonZero);
+
+        let line = [Vector2 { x: 12.0 + offset, y: 46.0 }, Vector2 { x: 30.0 + offset, y: 40.0 }];
+        let style = StrokeStyle { width: StrokeWidth::Screen(6.0), join: LineJoin::Miter, cap: caps[i] };
+        stroke_polyline(&mut rasterizer, camera, &line, style);
+        rasterizer.fill(canvas, 0xFFFF8000, FillRule::NonZero);
+
+        // The line the stroke is around, to see where the caps go
+        canvas.draw_line(line[0], line[1], 0xFF0000FF);
+        i += 1;
+    }
+
+    // Translucent, overlapping itself, it should come out the same everywhere along it
+    let loop_points = [
+        Vector2 { x: 10.0, y: 60.0 },
+        Vector2 { x: 50.0, y: 90.0 },
+        Vector2 { x: 50.0, y: 60.0 },
+        Vector2 { x: 10.0, y: 90.0 },
+        Vector2 { x: 12.0, y: 58.0 }
+    ];
+    let style = StrokeStyle { width: StrokeWidth::Screen(4.5), join: LineJoin::Round, cap: LineCap::Round };
+    stroke_polyline(&mut rasterizer, camera, &loop_points, style);
+    rasterizer.fill(canvas, 0x8000FF80, FillRule::NonZero);
+
+    // Curves, a thin one and a thick one in world units on a zoomed in camera
+    let curve = BezierCurve::new(
+        Vector2 { x: 64.0, y: 100.0 },
+        Vector2 { x: 80.0, y: 50.0 },
+        Vector2 { x: 110.0, y: 120.0 },
+        Vector2 { x: 122.0, y: 60.0 }
+    );
+    let style = StrokeStyle { width: StrokeWidth::Screen(1.0), join: LineJoin::Round, cap: LineCap::Butt };
+    stroke_bezier_curve(&mut rasterizer, camera, curve, style);
+    rasterizer.fill(canvas, 0xFFFFFF00, FillRule::NonZero);
+
+    let zoomed_camera = Camera { x: 32.0, y: 32.0, width: 64.0, height: 64.0, y_scale: 2.0 };
+    let curve = BezierCurve::new(
+        Vector2 { x: 2.0, y: 62.0 },
+        Vector2 { x: 10.0, y: 48.0 },
+        Vector2 { x: 24.0, y: 62.0 },
+        Vector2 { x: 30.0, y: 50.0 }
+    );
+    let style = StrokeStyle { width: StrokeWidth::World(3.0), join: LineJoin::Miter, cap: LineCap::Square };
+    stroke_bezier_curve(&mut rasterizer, zoomed_camera, curve, style);
+    rasterizer.fill(canvas, 0xC0FF00FF, FillRule::NonZero);
+
+    // Single points, which only show with round and square caps, and a stroke running off the edges
+    let point = [Vector2 { x: 100.0, y: 100.0 }];
+    stroke_polyline(&mut rasterizer, camera, &point, StrokeStyle { width: StrokeWidth::Screen(6.0), join: LineJoin::Miter, cap: LineCap::Round });
+    rasterizer.fill(canvas, 0xFF00FFFF, FillRule::NonZero);
+    let point = [Vector2 { x: 112.0, y: 100.0 }];
+    stroke_polyline(&mut rasterizer, camera, &point, StrokeStyle { width: StrokeWidth::Screen(6.0), join: LineJoin::Miter, cap: LineCap::Square });
+    rasterizer.fill(canvas, 0xFF00FFFF, FillRule::NonZero);
+
+    let off_edges = [Vector2 { x: -20.0, y: 140.0 }, Vector2 { x: 70.0, y: 118.0 }, Vector2 { x: 150.0, y: 150.0 }];
+    let style = StrokeStyle { width: StrokeWidth::Screen(8.0), join: LineJoin::Miter, cap: LineCap::Square };
+    stroke_polyline(&mut rasterizer, camera, &off_edges, style);
+    rasterizer.fill(canvas, 0xFF8080FF, FillRule::NonZero);
+
+    // A straight curve comes out as a single segment, this one runs off both sides at once
+    let across = BezierCurve::new(
+        Vector2 { x: -100.0, y: 52.0 },
+        Vector2 { x: -100.0, y: 52.0 },
+        Vector2 { x: 228.0, y: 53.0 },
+        Vector2 { x: 228.0, y: 53.0 }
+    );
+    let style = StrokeStyle { width: StrokeWidth::Screen(2.0), join: LineJoin::Round, cap: LineCap::Butt };
+    stroke_bezier_curve(&mut rasterizer, camera, across, style);
+    rasterizer.fill(canvas, 0xFFFF8080, FillRule::NonZero);
+}
+
+// Meant for a 128x128 canvas
+fn draw_fills_pattern(canvas: &mut Canvas) {
+    canvas.clear(0xFF000000);
+
+    let mut cells = vec![0.0; Rasterizer::cell_count(canvas.width(), canvas.height())];
+    let mut rasterizer = Rasterizer::new(&mut cells, canvas.width(), canvas.height());
+    let camera = pixel_camera(canvas);
+
+    // A five pointed star crosses over itself, the middle is inside for nonzero but not for even-odd
+    let star = |center: Vector2| {
+        let mut path = [PathSegment::Close; 6];
+        let mut i = 0;
+        while i < 5 {
+            let angle = (i * 2) as f32 * std::f32::consts::TAU / 5.0 - std::f32::consts::FRAC_PI_2;
+            let point = Vector2 { x: center.x + angle.cos() * 26.0, y: center.y + angle.sin() * 26.0 };
+            path[i] = if i == 0 { PathSegment::MoveTo(point) } else { PathSegment::LineTo(point) };
+            i += 1;
+        }
+        path
+    };
+
+    fill_path(&mut rasterizer, camera, &star(Vector2 { x: 32.3, y: 30.0 }));
+    rasterizer.fill(canvas, 0xFFFFFFFF, FillRule::NonZero);
+    fill_path(&mut rasterizer, camera, &star(Vector2 { x: 94.7, y: 30.0 }));
+    rasterizer.fill(canvas, 0xFFFFFFFF, FillRule::EvenOdd);
+
+    // Rings made of curves. With the inner circle going the other way both rules leave a hole,
+    // going the same way only even-odd does.
+    let ring = |center: Vector2, inner_reversed: bool| {
+        let mut path = [PathSegment::Close; 12];
+        add_circle_path(&mut path[..6], center, 20.0, false);
+        add_circle_path(&mut path[6..], center, 10.5, inner_reversed);
+        path
+    };
+
+    fill_path(&mut rasterizer, camera, &ring(Vector2 { x: 24.0, y: 82.0 }, true));
+    rasterizer.fill(canvas, 0xFF3399FF, FillRule::NonZero);
+    fill_path(&mut rasterizer, camera, &ring(Vector2 { x: 64.0, y: 82.0 }, false));
+    rasterizer.fill(canvas, 0xFF3399FF, FillRule::NonZero);
+    fill_path(&mut rasterizer, camera, &ring(Vector2 { x: 104.0, y: 82.0 }, false));
+    rasterizer.fill(canvas, 0xFF3399FF, FillRule::EvenOdd);
+
+    // Translucent over the rings, and left open so fill_path has to close it
+    let open_triangle = [
+        PathSegment::MoveTo(Vector2 { x: 10.0, y: 70.0 }),
+        PathSegment::LineTo(Vector2 { x: 118.0, y: 76.0 }),
+        PathSegment::LineTo(Vector2 { x: 60.0, y: 100.0 })
+    ];
+    fill_path(&mut rasterizer, camera, &open_triangle);
+    rasterizer.fill(canvas, 0x80FF6633, FillRule::NonZero);
+
+    // A curve closed by a straight line like the editor fills them, hanging off the bottom and left
+    let curve_shape = [
+        PathSegment::MoveTo(Vector2 { x: -20.0, y: 104.0 }),
+        PathSegment::CurveTo(Vector2 { x: 40.0, y: 90.0 }, Vector2 { x: 40.0, y: 150.0 }, Vector2 { x: 70.0, y: 140.0 }),
+        PathSegment::Close
+    ];
+    fill_path(&mut rasterizer, camera, &curve_shape);
+    rasterizer.fill(canvas, 0xFF66DD66, FillRule::NonZero);
+
+    // Thinner than a pixel, and a long sliver off the right side, both should come out faint
+    let sliver = [
+        PathSegment::MoveTo(Vector2 { x: 80.0, y: 110.0 }),
+        PathSegment::LineTo(Vector2 { x: 200.0, y: 110.3 }),
+        PathSegment::LineTo(Vector2 { x: 80.0, y: 110.6 })
+    ];
+    fill_path(&mut rasterizer, camera, &sliver);
+    rasterizer.fill(canvas, 0xFFFFFF00, FillRule::NonZero);
+
+    let speck = [
+        PathSegment::MoveTo(Vector2 { x: 100.2, y: 118.2 }),
+        PathSegment::LineTo(Vector2 { x: 100.8, y: 118.2 }),
+        PathSegment::LineTo(Vector2 { x: 100.8, y: 118.8 }),
+        PathSegment::LineTo(Vector2 { x: 100.2, y: 118.8 })
+    ];
+    fill_path(&mut rasterizer, camera, &speck);
+    rasterizer.fill(canvas, 0xFFFFFFFF, FillRule::NonZero);
+
+    // A band with edges running off both sides at once
+    let band = [
+        PathSegment::MoveTo(Vector2 { x: -20.0, y: 58.0 }),
+        PathSegment::LineTo(Vector2 { x: 150.0, y: 58.5 }),
+        PathSegment::LineTo(Vector2 { x: 150.0, y: 60.5 }),
+        PathSegment::LineTo(Vector2 { x: -20.0, y: 60.0 })
+    ];
+    fill_path(&mut rasterizer, camera, &band);
+    rasterizer.fill(canvas, 0xFFFF8080, FillRule::NonZero);
+}
+
+// Meant for a 128x128 canvas
+fn draw_curves_pattern(canvas: &mut Canvas) {
+    canvas.clear(0xFF000000);
+
+    let mut cells = vec![0.0; Rasterizer::cell_count(canvas.width(), canvas.height())];
+    let mut rasterizer = Rasterizer::new(&mut cells, canvas.width(), canvas.height());
+    let camera = pixel_camera(canvas);
+
+    // Flattened straight into one pixel lines, a tight loop and a wide S bend
+    let mut points = [Vector2::zero(); MAX_CURVE_SEGMENTS + 1];
+    let hairlines = [
+        BezierCurve::new(Vector2 { x: 4.0, y: 40.0 }, Vector2 { x: 70.0, y: 0.0 }, Vector2 { x: -10.0, y: 0.0 }, Vector2 { x: 56.0, y: 40.0 }),
+        BezierCurve::new(Vector2 { x: 66.0, y: 6.0 }, Vector2 { x: 150.0, y: 10.0 }, Vector2 { x: 40.0, y: 40.0 }, Vector2 { x: 124.0, y: 40.0 })
+    ];
+    for curve in hairlines {
+        let point_count = curve.flatten(CURVE_TOLERANCE, &mut points);
+        let mut i = 1;
+        while i < point_count {
+            canvas.draw_line(points[i - 1], points[i], 0xFFFFFFFF);
+            i += 1;
+        }
+    }
+
+    // The same small curve in world space seen through three zooms, it should look as smooth at
+    // every one of them
+    let curve = BezierCurve::new(
+        Vector2 { x: 0.0, y: 1.0 },
+        Vector2 { x: 0.4, y: 0.0 },
+        Vector2 { x: 0.6, y: 2.0 },
+        Vector2 { x: 1.0, y: 1.0 }
+    );
+    let zooms = [(8.0, 4.0), (24.0, 20.0), (64.0, 60.0)];
+    for (y_scale, screen_x) in zooms {
+        // Puts the curve's left end at screen_x, 80
+        let camera = Camera {
+            x: 64.0 / y_scale - screen_x / y_scale,
+            y: 64.0 / y_scale - 80.0 / y_scale + 1.0,
+            width: 128.0 / y_scale,
+            height: 128.0 / y_scale,
+            y_scale
+        };
+        let style = StrokeStyle { width: StrokeWidth::Screen(2.0), join: LineJoin::Round, cap: LineCap::Round };
+        stroke_bezier_curve(&mut rasterizer, camera, curve, style);
+        rasterizer.fill(canvas, 0xFFFF8000, FillRule::NonZero);
+    }
+
+    // Filled, with a loop where the curve crosses itself and bends hardest
+    let looped = [
+        PathSegment::MoveTo(Vector2 { x: 10.0, y: 124.0 }),
+        PathSegment::CurveTo(Vector2 { x: 80.0, y: 84.0 }, Vector2 { x: -20.0, y: 84.0 }, Vector2 { x: 50.0, y: 124.0 }),
+        PathSegment::Close
+    ];
+    fill_path(&mut rasterizer, camera, &looped);
+    rasterizer.fill(canvas, 0xFF3399FF, FillRule::NonZero);
+
+    // Curves with their control points on top of each other or on the ends, which are straight lines
+    // or points and should come out as a single segment
+    let straight = BezierCurve::new(Vector2 { x: 70.0, y: 120.0 }, Vector2 { x: 70.0, y: 120.0 }, Vector2 { x: 120.0, y: 100.0 }, Vector2 { x: 120.0, y: 100.0 });
+    let style = StrokeStyle { width: StrokeWidth::Screen(3.0), join: LineJoin::Miter, cap: LineCap::Butt };
+    stroke_bezier_curve(&mut rasterizer, camera, straight, style);
+    rasterizer.fill(canvas, 0xFF66DD66, FillRule::NonZero);
+
+    let point = Vector2 { x: 110.0, y: 120.0 };
+    let style = StrokeStyle { width: StrokeWidth::Screen(4.0), join: LineJoin::Miter, cap: LineCap::Round };
+    stroke_bezier_curve(&mut rasterizer, camera, BezierCurve::new(point, point, point, point), style);
+    rasterizer.fill(canvas, 0xFF66DD66, FillRule::NonZero);
+}
+
+// A closed circle out of four curves into path, which needs room for 6 segments
+fn add_circle_path(path: &mut [PathSegment], center: Vector2, radius: f32, clockwise: bool) {
+    // How far out the control points go for a curve to follow a quarter circle closely
+    let handle = radius * 0.552_284_8;
+    let flip = if clockwise { -1.0 } else { 1.0 };
+    let point = |x: f32, y: f32| Vector2 { x: center.x + x, y: center.y + y * flip };
+
+    path[0] = PathSegment::MoveTo(point(radius, 0.0));
+    path[1] = PathSegment::CurveTo(point(radius, handle), point(handle, radius), point(0.0, radius));
+    path[2] = PathSegment::CurveTo(point(-handle, radius), point(-radius, handle), point(-radius, 0.0));
+    path[3] = PathSegment::CurveTo(point(-radius, -handle), point(-handle, -radius), point(0.0, -radius));
+    path[4] = PathSegment::CurveTo(point(handle, -radius), point(radius, -handle), point(radius, 0.0));
+    path[5] = PathSegment::Close;
+}
+
+// World space is the same as screen space
+fn pixel_camera(canvas: &Canvas) -> Camera {
+    let width = canvas.width() as f32;
+    let height = canvas.height() as f32;
+    Camera { x: width / 2.0, y: height / 2.0, width, height, y_scale: 1.0 }
+}